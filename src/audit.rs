@@ -0,0 +1,98 @@
+use crate::constants;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub user: Option<String>,
+    pub channel: Option<String>,
+    pub action: String,
+    pub subject: String,
+    pub outcome: String
+}
+
+
+// Append-only record of destructive actions, one JSON object per line.
+pub struct Audit {
+    path: PathBuf,
+    lock: Mutex<()>
+}
+
+
+impl Audit {
+    pub fn new(path: PathBuf) -> Self {
+	Audit {
+	    path,
+	    lock: Mutex::new(())
+	}
+    }
+
+    pub fn from_env() -> Self {
+	Audit::new(
+	    env::var("RUSTY_BOT_AUDIT")
+		.unwrap_or_else(|_| constants::AUDIT_FILE.to_owned())
+		.into()
+	)
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+	let _guard = self.lock.lock().unwrap();
+	println!("Audit: {:?}", entry);
+	let line = match serde_json::to_string(&entry) {
+	    Ok(line) => line,
+	    Err(error) => return println!("Error serializing audit entry: {}", error),
+	};
+	let result = OpenOptions::new()
+	    .create(true)
+	    .append(true)
+	    .open(&self.path)
+	    .and_then(|mut file| writeln!(file, "{}", line));
+	if let Err(error) = result {
+	    println!("Error writing audit trail {:?}: {}", &self.path, error);
+	}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+
+    fn make_entry(subject: &str, outcome: &str) -> AuditEntry {
+	AuditEntry {
+	    at: Utc::now(),
+	    user: Some("U123".to_owned()),
+	    channel: Some("C456".to_owned()),
+	    action: "ec2 terminate".to_owned(),
+	    subject: subject.to_owned(),
+	    outcome: outcome.to_owned()
+	}
+    }
+
+
+    #[test]
+    fn test_audit_appends_entries() {
+	let path = env::temp_dir().join(format!("rustybot_audit_test_{}.log", std::process::id()));
+	let _ = fs::remove_file(&path);
+	let audit = Audit::new(path.clone());
+	audit.record(make_entry("i-01", "terminated"));
+	audit.record(make_entry("i-02", "refused: termination protection is enabled"));
+	let entries: Vec<AuditEntry> = fs::read_to_string(&path).unwrap()
+	    .lines()
+	    .map(|line| serde_json::from_str(line).unwrap())
+	    .collect();
+	assert_eq!(entries.len(), 2);
+	assert_eq!(entries[0].subject, "i-01");
+	assert_eq!(entries[1].outcome, "refused: termination protection is enabled");
+	let _ = fs::remove_file(path);
+    }
+}
@@ -1,4 +1,7 @@
-use rusoto_core::Region;
+use crate::constants;
+use chrono::{DateTime, Duration, Utc};
+use rusoto_core::credential::{AutoRefreshingProvider, DefaultCredentialsProvider, ProfileProvider, ProvideAwsCredentials};
+use rusoto_core::{HttpClient, Region, RusotoError};
 use rusoto_ec2::Ec2;
 use rusoto_ec2::Ec2Client;
 use rusoto_ec2::DescribeInstancesRequest;
@@ -6,14 +9,126 @@ use rusoto_ec2::StopInstancesRequest;
 use rusoto_ec2::StartInstancesRequest;
 use rusoto_ec2::InstanceStateChange;
 use rusoto_ec2::ModifyInstanceAttributeRequest;
+use rusoto_ec2::DescribeInstanceAttributeRequest;
+use rusoto_ec2::TerminateInstancesRequest;
+use rusoto_ec2::RebootInstancesRequest;
 use rusoto_ec2::AttributeValue;
 use rusoto_ec2::filter;
 use rusoto_ec2::Filter;
 use rusoto_ec2::Reservation;
 use rusoto_ec2::Instance;
+use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
+use std::env;
 use std::error::Error;
-use regex::Regex;
 use std::fmt;
+use std::str::FromStr;
+use regex::Regex;
+
+
+#[derive(Debug, PartialEq)]
+pub enum AwsError {
+    NotFound(String),
+    Ambiguous(String, Vec<InstanceInfo>),
+    PermissionDenied(String),
+    Throttled,
+    InvalidInstanceType(String),
+    UnexpectedState {
+	instance_id: String,
+	state: String
+    },
+    TimedOut {
+	instance_id: String,
+	state: String,
+	last_state: String
+    },
+    Cancelled,
+    Transport(String),
+    Rejected {
+	code: String,
+	message: String
+    }
+}
+
+
+impl fmt::Display for AwsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    AwsError::NotFound(url_or_id) => write!(f, "{} did not resolve to any instance", url_or_id),
+	    AwsError::Ambiguous(url_or_id, matches) => write!(
+		f,
+		"{} matches {} instances, use one of their IDs instead: {}",
+		url_or_id,
+		matches.len(),
+		matches.iter().map(InstanceInfo::summary).collect::<Vec<_>>().join(", ")
+	    ),
+	    AwsError::PermissionDenied(message) => write!(f, "The bot is not allowed to do that: {}", message),
+	    AwsError::Throttled => write!(f, "AWS is throttling requests, try again in a minute"),
+	    AwsError::InvalidInstanceType(size) => write!(f, "{} is not a valid instance type", size),
+	    AwsError::UnexpectedState { instance_id, state } => write!(f, "{} is {}", instance_id, state),
+	    AwsError::TimedOut { instance_id, state, last_state } => write!(
+		f,
+		"Timed out waiting for {} to be {}, it is {}",
+		instance_id,
+		state,
+		last_state
+	    ),
+	    AwsError::Cancelled => write!(f, "Cancelled"),
+	    AwsError::Transport(message) => write!(f, "Could not reach AWS: {}", message),
+	    AwsError::Rejected { code, message } => write!(f, "AWS rejected the request ({}): {}", code, message),
+	}
+    }
+}
+
+
+impl Error for AwsError {}
+
+
+// Classifies an EC2 error response such as
+// <Response><Errors><Error><Code>..</Code><Message>..</Message></Error></Errors></Response>.
+fn error_from_response(status: u16, body: &str) -> AwsError {
+    lazy_static! {
+	static ref CODE_RE: Regex = Regex::new(r"<Code>([^<]*)</Code>").unwrap();
+	static ref MESSAGE_RE: Regex = Regex::new(r"<Message>([^<]*)</Message>").unwrap();
+    }
+    let code = CODE_RE.captures(body).map(|capture| capture[1].to_owned()).unwrap_or_default();
+    let message = MESSAGE_RE.captures(body)
+	.map(|capture| capture[1].to_owned())
+	.unwrap_or_else(|| format!("HTTP {}", status));
+    match code.as_str() {
+	"UnauthorizedOperation" | "AuthFailure" | "Blocked" | "OptInRequired" => AwsError::PermissionDenied(message),
+	"RequestLimitExceeded" | "Throttling" => AwsError::Throttled,
+	"" if status == 403 => AwsError::PermissionDenied(message),
+	"" if status == 429 => AwsError::Throttled,
+	"" => AwsError::Transport(message),
+	_ => AwsError::Rejected { code, message },
+    }
+}
+
+
+impl<E: Error + 'static> From<RusotoError<E>> for AwsError {
+    fn from(error: RusotoError<E>) -> Self {
+	match error {
+	    RusotoError::Unknown(response) => error_from_response(response.status.as_u16(), response.body_as_str()),
+	    RusotoError::Credentials(error) => AwsError::PermissionDenied(error.to_string()),
+	    RusotoError::Validation(message) => AwsError::Rejected {
+		code: "ValidationError".to_owned(),
+		message
+	    },
+	    error => AwsError::Transport(error.to_string()),
+	}
+    }
+}
+
+
+const UNKNOWN: &str = "unknown";
+
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct BlockDevice {
+    pub device: String,
+    pub volume_id: String,
+    pub delete_on_termination: bool
+}
 
 
 #[derive(Debug, Eq, PartialEq)]
@@ -22,7 +137,15 @@ pub struct InstanceInfo {
     id: String,
     keyname: Option<String>,
     state: String,
-    tags: Vec<(String, String)>
+    tags: Vec<(String, String)>,
+    launch_time: Option<String>,
+    private_ip: Option<String>,
+    public_ip: Option<String>,
+    dns_name: Option<String>,
+    availability_zone: Option<String>,
+    image_id: Option<String>,
+    state_reason: Option<String>,
+    volumes: Vec<BlockDevice>
 }
 
 
@@ -47,7 +170,7 @@ fn make_describe_instances_request_with_filters(filters: Vec<Filter>) -> Describ
 
 fn make_stop_instances_request(instance_ids: Vec<String>) -> StopInstancesRequest {
     StopInstancesRequest {
-	instance_ids: instance_ids,
+	instance_ids,
 	..Default::default()
     }
 }
@@ -55,7 +178,7 @@ fn make_stop_instances_request(instance_ids: Vec<String>) -> StopInstancesReques
 
 fn make_start_instances_request(instance_ids: Vec<String>) -> StartInstancesRequest {
     StartInstancesRequest {
-	instance_ids: instance_ids,
+	instance_ids,
 	..Default::default()
     }
 }
@@ -74,52 +197,245 @@ fn make_modify_instance_type_request(instance_id: String, size: String) -> Modif
 }
 
 
-pub fn make_ec2_client() -> Ec2Client {
-    Ec2Client::new(Region::UsWest2)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub name: String,
+    pub role_arn: String
+}
+
+
+// Parses `name=role_arn` pairs separated by commas.
+pub fn parse_accounts(value: &str) -> Vec<Account> {
+    value.split(',')
+	.filter_map(|account| account.split_once('='))
+	.map(|(name, role_arn)| Account {
+	    name: name.trim().to_owned(),
+	    role_arn: role_arn.trim().to_owned()
+	})
+	.filter(|account| !account.name.is_empty() && !account.role_arn.is_empty())
+	.collect()
+}
+
+
+pub fn parse_regions(value: &str) -> Result<Vec<Region>, String> {
+    let regions = value.split(',')
+	.map(str::trim)
+	.filter(|region| !region.is_empty())
+	.map(|region| Region::from_str(region).map_err(|_| format!("Unknown region `{}`", region)))
+	.collect::<Result<Vec<Region>, String>>()?;
+    if regions.is_empty() {
+	return Err("Expected at least one region".to_owned());
+    }
+    Ok(regions)
+}
+
+
+// Parses `key` or `key=value` entries separated by commas. A bare key
+// protects any value.
+pub fn parse_protected_tags(value: &str) -> Vec<(String, Option<String>)> {
+    value.split(',')
+	.map(str::trim)
+	.filter(|tag| !tag.is_empty())
+	.map(|tag| match tag.split_once('=') {
+	    Some((key, value)) => (key.trim().to_owned(), Some(value.trim().to_owned())),
+	    None => (tag.to_owned(), None),
+	})
+	.collect()
 }
 
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct AwsConfig {
+    pub regions: Vec<Region>,
+    pub accounts: Vec<Account>,
+    pub protected_tags: Vec<(String, Option<String>)>
+}
+
+
+impl AwsConfig {
+    pub fn from_env() -> Self {
+	let regions = env::var("RUSTY_BOT_AWS_REGIONS").unwrap_or_else(|_| constants::DEFAULT_AWS_REGIONS.to_owned());
+	AwsConfig {
+	    regions: parse_regions(&regions).unwrap_or_else(|error| {
+		println!("Ignoring RUSTY_BOT_AWS_REGIONS: {}", error);
+		parse_regions(constants::DEFAULT_AWS_REGIONS).unwrap()
+	    }),
+	    accounts: parse_accounts(&env::var("RUSTY_BOT_AWS_ACCOUNTS").unwrap_or_default()),
+	    protected_tags: parse_protected_tags(
+		&env::var("RUSTY_BOT_PROTECTED_TAGS").unwrap_or_else(|_| constants::DEFAULT_PROTECTED_TAGS.to_owned())
+	    )
+	}
+    }
+
+    pub fn default_region(&self) -> Region {
+	self.regions[0].clone()
+    }
+
+    pub fn account(&self, name: &str) -> Result<Account, String> {
+	self.accounts.iter()
+	    .find(|account| account.name == name)
+	    .cloned()
+	    .ok_or_else(|| {
+		let names: Vec<&str> = self.accounts.iter().map(|account| account.name.as_str()).collect();
+		if names.is_empty() {
+		    format!("Unknown account `{}`, none are configured in RUSTY_BOT_AWS_ACCOUNTS", name)
+		} else {
+		    format!("Unknown account `{}`, expected one of {}", name, names.join(", "))
+		}
+	    })
+    }
+}
+
+
+// Where EC2 calls go: a region, plus optional credentials profile and
+// account whose role is assumed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub region: Region,
+    pub profile: Option<String>,
+    pub account: Option<Account>
+}
+
+
+impl Default for Target {
+    fn default() -> Self {
+	Target {
+	    region: Region::UsWest2,
+	    profile: None,
+	    account: None
+	}
+    }
+}
+
+
+impl Target {
+    pub fn label(&self) -> String {
+	let account = self.account.as_ref()
+	    .map(|account| account.name.as_str())
+	    .or(self.profile.as_deref())
+	    .unwrap_or("default");
+	format!("{}/{}", account, self.region.name())
+    }
+}
+
+
+fn make_http_client() -> Result<HttpClient, AwsError> {
+    HttpClient::new().map_err(|error| AwsError::Transport(error.to_string()))
+}
+
+
+fn make_ec2_client_with<P>(target: &Target, credentials: P) -> Result<Ec2Client, AwsError>
+where
+    P: ProvideAwsCredentials + Send + Sync + 'static
+{
+    let account = match &target.account {
+	Some(account) => account,
+	None => return Ok(Ec2Client::new_with(make_http_client()?, credentials, target.region.clone())),
+    };
+    let sts = StsClient::new_with(make_http_client()?, credentials, target.region.clone());
+    let role = StsAssumeRoleSessionCredentialsProvider::new(
+	sts,
+	account.role_arn.clone(),
+	constants::ROLE_SESSION_NAME.to_owned(),
+	None,
+	None,
+	None,
+	None
+    );
+    let role = AutoRefreshingProvider::new(role)
+	.map_err(|error| AwsError::PermissionDenied(error.to_string()))?;
+    Ok(Ec2Client::new_with(make_http_client()?, role, target.region.clone()))
+}
+
+
+pub fn make_ec2_client(target: &Target) -> Result<Ec2Client, AwsError> {
+    match &target.profile {
+	Some(profile) => {
+	    let mut credentials = ProfileProvider::new()
+		.map_err(|error| AwsError::PermissionDenied(error.to_string()))?;
+	    credentials.set_profile(profile.as_str());
+	    make_ec2_client_with(target, credentials)
+	},
+	None => {
+	    let credentials = DefaultCredentialsProvider::new()
+		.map_err(|error| AwsError::PermissionDenied(error.to_string()))?;
+	    make_ec2_client_with(target, credentials)
+	},
+    }
+}
+
+
+// Follows `next_token` until every page of results has been read.
 #[tokio::main]
-async fn describe_instances_and_unwrap_reservations(ec2: &Ec2Client, request: DescribeInstancesRequest) -> Result<Vec<Reservation>, Box<dyn Error>> {
-    let reservations = ec2
-	.describe_instances(request)
-	.await?
-	.reservations
-	.ok_or("No reservations")?;
-    Ok(reservations)
+async fn describe_instances_and_unwrap_reservations(ec2: &Ec2Client, mut request: DescribeInstancesRequest) -> Result<Vec<Reservation>, AwsError> {
+    let mut reservations = vec![];
+    loop {
+	let result = ec2
+	    .describe_instances(request.clone())
+	    .await?;
+	reservations.extend(result.reservations.unwrap_or_default());
+	match non_empty(result.next_token) {
+	    Some(next_token) => request.next_token = Some(next_token),
+	    None => return Ok(reservations),
+	}
+    }
 }
 
 
 #[tokio::main]
-async fn stop_instances_and_unwrap_stopped_instances(ec2: &Ec2Client, request: StopInstancesRequest) -> Result<Vec<InstanceStateChange>, Box<dyn Error>> {
+async fn stop_instances_and_unwrap_stopped_instances(ec2: &Ec2Client, request: StopInstancesRequest) -> Result<Vec<InstanceStateChange>, AwsError> {
     let stopped_instances = ec2
 	.stop_instances(request)
 	.await?
 	.stopping_instances
-	.ok_or("No instances stopped")?;
+	.unwrap_or_default();
     Ok(stopped_instances)
 }
 
 
 #[tokio::main]
-async fn start_instances_and_unwrap_started_instances(ec2: &Ec2Client, request: StartInstancesRequest) -> Result<Vec<InstanceStateChange>, Box<dyn Error>> {
+async fn start_instances_and_unwrap_started_instances(ec2: &Ec2Client, request: StartInstancesRequest) -> Result<Vec<InstanceStateChange>, AwsError> {
     let started_instances = ec2
 	.start_instances(request)
 	.await?
 	.starting_instances
-	.ok_or("No instances started")?;
+	.unwrap_or_default();
     Ok(started_instances)
 }
 
 
 #[tokio::main]
-async fn modify_instance_attribute(ec2: &Ec2Client, request: ModifyInstanceAttributeRequest) -> Result<(), Box<dyn Error>> {
-    let modified_instance = ec2.modify_instance_attribute(request).await?;
-    Ok(modified_instance)
+async fn modify_instance_attribute(ec2: &Ec2Client, request: ModifyInstanceAttributeRequest) -> Result<(), AwsError> {
+    ec2.modify_instance_attribute(request).await?;
+    Ok(())
+}
+
+
+#[tokio::main]
+async fn reboot_instances(ec2: &Ec2Client, request: RebootInstancesRequest) -> Result<(), AwsError> {
+    ec2.reboot_instances(request).await?;
+    Ok(())
+}
+
+
+#[tokio::main]
+async fn terminate_instances_and_unwrap_terminated_instances(ec2: &Ec2Client, request: TerminateInstancesRequest) -> Result<Vec<InstanceStateChange>, AwsError> {
+    let terminated_instances = ec2
+	.terminate_instances(request)
+	.await?
+	.terminating_instances
+	.unwrap_or_default();
+    Ok(terminated_instances)
 }
 
 
-fn get_instances_by_filters(ec2: &Ec2Client, filters: Vec<Filter>) -> Result<Vec<Instance>, Box<dyn Error>> {
+#[tokio::main]
+async fn describe_instance_attribute(ec2: &Ec2Client, request: DescribeInstanceAttributeRequest) -> Result<rusoto_ec2::InstanceAttribute, AwsError> {
+    Ok(ec2.describe_instance_attribute(request).await?)
+}
+
+
+fn get_instances_by_filters(ec2: &Ec2Client, filters: Vec<Filter>) -> Result<Vec<Instance>, AwsError> {
     let request = make_describe_instances_request_with_filters(filters);
     let reservations = describe_instances_and_unwrap_reservations(ec2, request);
     let matching_instances = flatten_reservations(reservations?);
@@ -127,40 +443,103 @@ fn get_instances_by_filters(ec2: &Ec2Client, filters: Vec<Filter>) -> Result<Vec
 }
 
 
-fn stop_instances_by_ids(ec2: &Ec2Client, instance_ids: Vec<String>) -> Result<Vec<InstanceStateChange>, Box<dyn Error>> {
+fn stop_instances_by_ids(ec2: &Ec2Client, instance_ids: Vec<String>) -> Result<Vec<InstanceStateChange>, AwsError> {
     let request = make_stop_instances_request(instance_ids);
     stop_instances_and_unwrap_stopped_instances(ec2, request)
 }
 
 
-fn start_instances_by_ids(ec2: &Ec2Client, instance_ids: Vec<String>) -> Result<Vec<InstanceStateChange>, Box<dyn Error>> {
+fn start_instances_by_ids(ec2: &Ec2Client, instance_ids: Vec<String>) -> Result<Vec<InstanceStateChange>, AwsError> {
     let request = make_start_instances_request(instance_ids);
     start_instances_and_unwrap_started_instances(ec2, request)
 }
 
 
-fn resize_instance_by_id(ec2: &Ec2Client, instance_id: String, size: String) -> Result<(), Box<dyn Error>> {
-    let request = make_modify_instance_type_request(instance_id, size);
-    modify_instance_attribute(ec2, request)
+fn resize_instance_by_id(ec2: &Ec2Client, instance_id: String, size: String) -> Result<(), AwsError> {
+    let request = make_modify_instance_type_request(instance_id, size.clone());
+    modify_instance_attribute(ec2, request).map_err(|error| match error {
+	// EC2 reports unknown types as a generic invalid parameter.
+	AwsError::Rejected { ref message, .. } if message.contains("instanceType") => AwsError::InvalidInstanceType(size),
+	error => error,
+    })
 }
 
 
-fn get_info_from_instances(instances: Vec<Instance>) -> Vec<InstanceInfo> {
-    let mut info: Vec<InstanceInfo> = vec![];
-    for instance in instances {
-	info.push(
-	    InstanceInfo {
-		size: instance.instance_type.unwrap(),
-		id: instance.instance_id.unwrap(),
-		keyname: instance.key_name,
-		state: instance.state.unwrap().name.unwrap(),
-		tags: instance.tags.unwrap().into_iter().map(
-		    |x| (x.key.unwrap(), x.value.unwrap())
-		).collect::<Vec<(String, String)>>()
-	    }
-	)
+impl InstanceInfo {
+    pub fn volumes(&self) -> &[BlockDevice] {
+	&self.volumes
+    }
+
+    // Returns the first tag matching `protected_tags`, as `key=value`.
+    pub fn protected_by(&self, protected_tags: &[(String, Option<String>)]) -> Option<String> {
+	self.tags.iter()
+	    .find(|(key, value)| protected_tags.iter().any(|(protected_key, protected_value)| {
+		protected_key == key && protected_value.as_ref().map(|protected_value| protected_value == value).unwrap_or(true)
+	    }))
+	    .map(|(key, value)| format!("{}={}", key, value))
+    }
+
+    pub fn state(&self) -> &str {
+	&self.state
+    }
+
+    // The demo URL behind the `Name` tag, the inverse of how URLs resolve.
+    pub fn demo_url(&self) -> Option<String> {
+	let (_, name) = self.tags.iter().find(|(key, _)| key == "Name")?;
+	let url = format!("https://{}.{}/", name, constants::DEMO_DOMAIN);
+	match parse_name_from_url(url.clone()) {
+	    Some(parsed) if parsed == *name => Some(url),
+	    _ => None,
+	}
     }
-    info
+
+    pub fn summary(&self) -> String {
+	let mut details = vec![self.state.as_str(), self.size.as_str()];
+	if let Some((_, name)) = self.tags.iter().find(|(key, _)| key == "Name") {
+	    details.push(name);
+	}
+	format!("{} ({})", self.id, details.join(", "))
+    }
+}
+
+
+// EC2 sends empty elements such as <dnsName/> for unset values.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
+}
+
+
+// Instances without an ID cannot be acted on and are skipped.
+fn get_info_from_instances(instances: Vec<Instance>) -> Vec<InstanceInfo> {
+    instances.into_iter().filter_map(|instance| {
+	Some(InstanceInfo {
+	    size: instance.instance_type.unwrap_or_else(|| UNKNOWN.to_owned()),
+	    id: non_empty(instance.instance_id)?,
+	    keyname: non_empty(instance.key_name),
+	    state: instance.state
+		.and_then(|state| state.name)
+		.unwrap_or_else(|| UNKNOWN.to_owned()),
+	    tags: instance.tags.unwrap_or_default().into_iter().filter_map(
+		|x| Some((x.key?, x.value.unwrap_or_default()))
+	    ).collect::<Vec<(String, String)>>(),
+	    launch_time: non_empty(instance.launch_time),
+	    private_ip: non_empty(instance.private_ip_address),
+	    public_ip: non_empty(instance.public_ip_address),
+	    dns_name: non_empty(instance.public_dns_name).or(non_empty(instance.private_dns_name)),
+	    availability_zone: non_empty(instance.placement.and_then(|placement| placement.availability_zone)),
+	    image_id: non_empty(instance.image_id),
+	    state_reason: non_empty(instance.state_reason.and_then(|reason| reason.message))
+		.or(non_empty(instance.state_transition_reason)),
+	    volumes: instance.block_device_mappings.unwrap_or_default().into_iter().filter_map(|mapping| {
+		let ebs = mapping.ebs?;
+		Some(BlockDevice {
+		    device: mapping.device_name.unwrap_or_else(|| UNKNOWN.to_owned()),
+		    volume_id: non_empty(ebs.volume_id)?,
+		    delete_on_termination: ebs.delete_on_termination.unwrap_or(false)
+		})
+	    }).collect()
+	})
+    }).collect()
 }
 
 
@@ -175,105 +554,330 @@ fn parse_name_from_url(url: String) -> Option<String> {
 
 fn is_instance_id(value: String) -> bool {
     let instance_id_re: Regex = Regex::new(r"^i-[0-9][0-9a-zA-Z]*").unwrap();
-    if let Some(capture) = instance_id_re.captures(&value) {
-	return true;
+    instance_id_re.is_match(&value)
+}
+
+
+pub fn is_valid_instance_type(size: &str) -> bool {
+    lazy_static! {
+        static ref INSTANCE_TYPE_RE: Regex = Regex::new(
+	    r"^[a-z][a-z0-9-]*\.(nano|micro|small|medium|large|metal|[0-9]*xlarge|metal-[0-9]+xl)$"
+	).unwrap();
     }
-    false
+    INSTANCE_TYPE_RE.is_match(size)
 }
 
 
-fn get_instance_info_from_name(ec2: &Ec2Client, name: String) -> Vec<InstanceInfo> {
-    let instances = get_instances_by_filters(
-	ec2,
-	vec![filter!("tag:Name", name)],
-    ).unwrap();
-    get_info_from_instances(instances)
+fn make_filters_from_tuples(filters: Vec<(String, String)>) -> Vec<Filter> {
+    filters.iter().map(
+	|f| filter!(f.0, f.1)
+    ).collect::<Vec<Filter>>()
 }
 
 
-fn get_instance_info_from_id(ec2: &Ec2Client, id: String) -> Vec<InstanceInfo> {
+pub fn get_instance_info_from_filters(ec2: &Ec2Client, filters: Vec<(String, String)>) -> Result<Vec<InstanceInfo>, AwsError> {
+    let filters = make_filters_from_tuples(filters);
     let instances = get_instances_by_filters(
 	ec2,
-	vec![filter!("instance-id", id)],
-    ).unwrap();
-    get_info_from_instances(instances)
+	filters,
+    )?;
+    Ok(get_info_from_instances(instances))
 }
 
 
-fn make_filters_from_tuples(filters: Vec<(String, String)>) -> Vec<Filter> {
-    filters.iter().map(
-	|f| filter!(f.0, f.1)
-    ).collect::<Vec<Filter>>()
+// The ways a user can refer to an instance, tried in this order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Identifier {
+    Url(String),
+    InstanceId(String),
+    User(String),
+    Ticket(String),
+    Commit(String),
+    DemoName(String)
 }
 
 
-fn get_instance_ids_from_url_or_id(ec2: &Ec2Client, url_or_id: String) -> Vec<String>{
-    let mut instance_ids = vec![];
-    if let Some(name) = parse_name_from_url(url_or_id.clone()) {
-	let instance_info = get_instance_info_from_name(ec2, name);
-	instance_ids = instance_info.iter().map(
-	    |x| x.id.to_owned()
-	).collect::<Vec<String>>();
-    } else if is_instance_id(url_or_id.clone()) {
-	instance_ids = vec![url_or_id];
+impl Identifier {
+    pub fn parse(value: &str) -> Option<Identifier> {
+	lazy_static! {
+	    static ref USER_RE: Regex = Regex::new(r"^@([a-zA-Z][-0-9a-zA-Z_.]*)$").unwrap();
+	    static ref TICKET_RE: Regex = Regex::new(r"^[a-zA-Z]+-[0-9]+$").unwrap();
+	    static ref COMMIT_RE: Regex = Regex::new(r"^[0-9a-f]{7,40}$").unwrap();
+	}
+	let value = value.trim();
+	if let Some(name) = parse_name_from_url(value.to_owned()) {
+	    return Some(Identifier::Url(name));
+	}
+	if is_instance_id(value.to_owned()) {
+	    return Some(Identifier::InstanceId(value.to_owned()));
+	}
+	if let Some(capture) = USER_RE.captures(value) {
+	    return Some(Identifier::User(capture[1].to_owned()));
+	}
+	if TICKET_RE.is_match(value) {
+	    return Some(Identifier::Ticket(value.to_uppercase()));
+	}
+	if COMMIT_RE.is_match(value) {
+	    return Some(Identifier::Commit(value.to_owned()));
+	}
+	Identifier::parse_demo_name(value)
+    }
+
+    fn parse_demo_name(value: &str) -> Option<Identifier> {
+	lazy_static! {
+	    static ref DEMO_NAME_RE: Regex = Regex::new(r"^[a-z][-0-9a-z_]*$").unwrap();
+	}
+	let value = value.trim();
+	if DEMO_NAME_RE.is_match(value) {
+	    return Some(Identifier::DemoName(value.to_owned()));
+	}
+	None
+    }
+
+    // A demo named like a ticket, e.g. `test-123`, parses as a ticket first
+    // and is looked up by name when no branch matches it.
+    fn fallback(&self, value: &str) -> Option<Identifier> {
+	match self {
+	    Identifier::Ticket(_) => Identifier::parse_demo_name(value),
+	    _ => None,
+	}
+    }
+
+    pub fn rule(&self) -> &'static str {
+	match self {
+	    Identifier::Url(_) => "demo URL",
+	    Identifier::InstanceId(_) => "instance ID",
+	    Identifier::User(_) => "started_by tag",
+	    Identifier::Ticket(_) => "Jira ticket in the branch tag",
+	    Identifier::Commit(_) => "commit hash prefix",
+	    Identifier::DemoName(_) => "demo name",
+	}
+    }
+
+    fn filter(&self) -> Filter {
+	match self {
+	    Identifier::Url(name) | Identifier::DemoName(name) => filter!("tag:Name", name),
+	    Identifier::InstanceId(id) => filter!("instance-id", id),
+	    Identifier::User(user) => filter!("tag:started_by", user),
+	    Identifier::Ticket(ticket) => {
+		let lower = ticket.to_lowercase();
+		filter!("tag:branch", ticket, format!("{}-*", ticket), lower, format!("{}-*", lower))
+	    },
+	    // Tags hold abbreviated hashes, so match on a short prefix and
+	    // compare the full value afterwards.
+	    Identifier::Commit(hash) => filter!("tag:commit", format!("{}*", &hash[..7])),
+	}
+    }
+
+    fn matches(&self, info: &InstanceInfo) -> bool {
+	let tag = |key: &str| info.tags.iter()
+	    .find(|(tag_key, _)| tag_key == key)
+	    .map(|(_, value)| value.as_str());
+	match self {
+	    Identifier::Url(name) | Identifier::DemoName(name) => tag("Name") == Some(name.as_str()),
+	    Identifier::InstanceId(id) => &info.id == id,
+	    Identifier::User(user) => tag("started_by") == Some(user.as_str()),
+	    Identifier::Ticket(ticket) => tag("branch")
+		.map(|branch| branch.to_uppercase())
+		.map(|branch| branch == *ticket || branch.starts_with(&format!("{}-", ticket)))
+		.unwrap_or(false),
+	    Identifier::Commit(hash) => tag("commit")
+		.map(|commit| !commit.is_empty() && (commit.starts_with(hash.as_str()) || hash.starts_with(commit)))
+		.unwrap_or(false),
+	}
     }
-    instance_ids
 }
 
 
-pub fn get_instance_info_from_filters(ec2: &Ec2Client, filters: Vec<(String, String)>) -> Result<Vec<InstanceInfo>, Box<dyn Error>> {
-    let filters = make_filters_from_tuples(filters);
-    let instances = get_instances_by_filters(
-	&ec2,
-	filters,
-    )?;
-    Ok(get_info_from_instances(instances))
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    NoMatch,
+    One(Box<InstanceInfo>),
+    Many(Vec<InstanceInfo>)
+}
+
+
+impl Resolution {
+    fn from_matches(mut matches: Vec<InstanceInfo>) -> Self {
+	match matches.len() {
+	    0 => Resolution::NoMatch,
+	    1 => Resolution::One(Box::new(matches.remove(0))),
+	    _ => Resolution::Many(matches),
+	}
+    }
+}
+
+
+#[derive(Debug, PartialEq)]
+pub struct Resolved {
+    pub identifier: Identifier,
+    pub instance: InstanceInfo
 }
 
 
-pub fn get_instance_info_from_url_or_id(ec2: &Ec2Client, url_or_id: String) -> Vec<InstanceInfo> {
-    let mut instance_info = vec![];
-    if let Some(name) = parse_name_from_url(url_or_id.clone()) {
-	instance_info = get_instance_info_from_name(ec2, name);
-    } else if is_instance_id(url_or_id.clone()) {
-	instance_info = get_instance_info_from_id(ec2, url_or_id.clone());
+impl Resolved {
+    pub fn id(&self) -> &str {
+	&self.instance.id
+    }
+
+    pub fn describe(&self) -> String {
+	format!("{}, matched by {}", self.instance.summary(), self.identifier.rule())
     }
-    instance_info
 }
 
 
-pub fn stop_instance_by_url_or_id(ec2: &Ec2Client, url_or_id: String) -> Result<Vec<InstanceStateChange>, Box<dyn Error>> {
-    let instance_ids = get_instance_ids_from_url_or_id(ec2, url_or_id);
-    stop_instances_by_ids(ec2, instance_ids)
+// Looks up the instances `value` refers to, ignoring terminated ones since
+// their names and tags live on after they are gone.
+pub fn resolve(ec2: &Ec2Client, value: &str) -> Result<(Option<Identifier>, Resolution), AwsError> {
+    let identifier = match Identifier::parse(value) {
+	Some(identifier) => identifier,
+	None => return Ok((None, Resolution::NoMatch)),
+    };
+    let resolution = resolve_identifier(ec2, &identifier)?;
+    if resolution == Resolution::NoMatch {
+	if let Some(fallback) = identifier.fallback(value) {
+	    let resolution = resolve_identifier(ec2, &fallback)?;
+	    return Ok((Some(fallback), resolution));
+	}
+    }
+    Ok((Some(identifier), resolution))
 }
 
 
-pub fn start_instance_by_url_or_id(ec2: &Ec2Client, url_or_id: String) -> Result<Vec<InstanceStateChange>, Box<dyn Error>> {
-    let instance_ids = get_instance_ids_from_url_or_id(ec2, url_or_id);
-    start_instances_by_ids(ec2, instance_ids)
+fn resolve_identifier(ec2: &Ec2Client, identifier: &Identifier) -> Result<Resolution, AwsError> {
+    let instance_info = get_info_from_instances(get_instances_by_filters(ec2, vec![identifier.filter()])?);
+    let matches = instance_info.into_iter()
+	.filter(|info| info.state != "terminated" && identifier.matches(info))
+	.collect();
+    Ok(Resolution::from_matches(matches))
+}
+
+
+// Resolves `value` to exactly one instance.
+pub fn resolve_instance(ec2: &Ec2Client, value: &str) -> Result<Resolved, AwsError> {
+    match resolve(ec2, value)? {
+	(Some(identifier), Resolution::One(instance)) => Ok(Resolved { identifier, instance: *instance }),
+	(_, Resolution::Many(matches)) => Err(AwsError::Ambiguous(value.to_owned(), matches)),
+	_ => Err(AwsError::NotFound(value.to_owned())),
+    }
+}
+
+
+// Unlike `resolve_instance` this sees terminated instances, so a wait can
+// tell an instance that went away from one that never existed.
+fn get_instance_state_by_id(ec2: &Ec2Client, instance_id: &str) -> Result<String, AwsError> {
+    get_info_from_instances(get_instances_by_filters(ec2, vec![filter!("instance-id", instance_id)])?)
+	.into_iter()
+	.find(|info| info.id == instance_id)
+	.map(|info| info.state)
+	.ok_or_else(|| AwsError::NotFound(instance_id.to_owned()))
+}
+
+
+// Polls `instance_id` until it is in `state`, calling `on_transition` with
+// each newly seen state and how long it took to get there. `sleep_until` waits
+// between polls and returns false to cancel. Throttling and transport errors
+// are retried until `timeout` passes. Returns how long the wait took.
+pub fn wait_for_state<S, T>(ec2: &Ec2Client, instance_id: &str, state: &str, timeout: Duration, mut sleep_until: S, mut on_transition: T) -> Result<Duration, AwsError>
+where
+    S: FnMut(DateTime<Utc>) -> bool,
+    T: FnMut(&str, Duration)
+{
+    let started = Utc::now();
+    let deadline = started.checked_add_signed(timeout).unwrap_or(DateTime::<Utc>::MAX_UTC);
+    let mut last_state: Option<String> = None;
+    loop {
+	match get_instance_state_by_id(ec2, instance_id) {
+	    Ok(current) => {
+		if last_state.as_ref() != Some(&current) {
+		    on_transition(&current, Utc::now() - started);
+		}
+		if current == state {
+		    return Ok(Utc::now() - started);
+		}
+		if current == "shutting-down" || current == "terminated" {
+		    return Err(AwsError::UnexpectedState { instance_id: instance_id.to_owned(), state: current });
+		}
+		last_state = Some(current);
+	    },
+	    Err(AwsError::Throttled) | Err(AwsError::Transport(_)) => {},
+	    Err(error) => return Err(error),
+	}
+	let now = Utc::now();
+	if now >= deadline {
+	    return Err(AwsError::TimedOut {
+		instance_id: instance_id.to_owned(),
+		state: state.to_owned(),
+		last_state: last_state.unwrap_or_else(|| UNKNOWN.to_owned())
+	    });
+	}
+	if !sleep_until((now + Duration::seconds(constants::WAITUP_INTERVAL)).min(deadline)) {
+	    return Err(AwsError::Cancelled);
+	}
+    }
+}
+
+
+pub fn stop_instance(ec2: &Ec2Client, resolved: &Resolved) -> Result<Vec<InstanceStateChange>, AwsError> {
+    stop_instances_by_ids(ec2, vec![resolved.id().to_owned()])
+}
+
+
+pub fn start_instance(ec2: &Ec2Client, resolved: &Resolved) -> Result<Vec<InstanceStateChange>, AwsError> {
+    start_instances_by_ids(ec2, vec![resolved.id().to_owned()])
+}
+
+
+pub fn reboot_instance(ec2: &Ec2Client, resolved: &Resolved) -> Result<(), AwsError> {
+    let request = RebootInstancesRequest {
+	instance_ids: vec![resolved.id().to_owned()],
+	..Default::default()
+    };
+    reboot_instances(ec2, request)
+}
+
+
+pub fn has_termination_protection(ec2: &Ec2Client, resolved: &Resolved) -> Result<bool, AwsError> {
+    let request = DescribeInstanceAttributeRequest {
+	attribute: "disableApiTermination".to_owned(),
+	instance_id: resolved.id().to_owned(),
+	..Default::default()
+    };
+    let attribute = describe_instance_attribute(ec2, request)?;
+    Ok(attribute.disable_api_termination.and_then(|value| value.value).unwrap_or(false))
+}
+
+
+pub fn terminate_instance(ec2: &Ec2Client, resolved: &Resolved) -> Result<Vec<InstanceStateChange>, AwsError> {
+    let request = TerminateInstancesRequest {
+	instance_ids: vec![resolved.id().to_owned()],
+	..Default::default()
+    };
+    terminate_instances_and_unwrap_terminated_instances(ec2, request)
 }
 
 
-pub fn resize_instance_by_url_or_id(ec2: &Ec2Client, url_or_id: String, size: String) -> Result<(), Box<dyn Error>> {
-    if let Some(instance_id) = get_instance_ids_from_url_or_id(ec2, url_or_id).pop() {
-	return resize_instance_by_id(ec2, instance_id.clone(), size.clone());
+pub fn resize_instance(ec2: &Ec2Client, resolved: &Resolved, size: String) -> Result<(), AwsError> {
+    if !is_valid_instance_type(&size) {
+	return Err(AwsError::InvalidInstanceType(size));
     }
-    Err("No instances found".into())
+    resize_instance_by_id(ec2, resolved.id().to_owned(), size)
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rusoto_core::signature::{SignedRequest, SignedRequestPayload};
     use rusoto_mock::{
 	MockCredentialsProvider,
 	MockRequestDispatcher,
+	MultipleMockRequestDispatcher,
     };
 
 
     const DESCRIBE_INSTANCES_BODY: &str =
-            r#"
-            <?xml version="1.0" encoding="UTF-8"?><DescribeInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2014-06-15/">
+            r#"<?xml version="1.0" encoding="UTF-8"?><DescribeInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2014-06-15/">
             <requestId>d15d204f-fc31-4600-85d3-5c86e5483b92</requestId><reservationSet><item><reservationId>r-9b4f3ca8</reservationId>
             <ownerId>123456789012</ownerId><groupSet><item><groupId>sg-4e970e7e</groupId><groupName>notebook</groupName></item></groupSet>
             <instancesSet><item><instanceId>i-0c3cbd3a6e1b8ffc8</instanceId><imageId>ami-30fe7300</imageId><instanceState><code>80</code>
@@ -334,6 +938,20 @@ mod tests {
 		("commit".to_owned(), "3a048a0ae".to_owned()),
 		("started_by".to_owned(), "emma".to_owned()),
 		("Name".to_owned(), "encd-5328-3a048a0ae-emma".to_owned())
+	    ],
+	    launch_time: Some("2012-10-16T20:00:13.000Z".to_owned()),
+	    private_ip: None,
+	    public_ip: None,
+	    dns_name: None,
+	    availability_zone: Some("us-west-2".to_owned()),
+	    image_id: Some("ami-30fe7300".to_owned()),
+	    state_reason: Some("Client.UserInitiatedShutdown: User initiated shutdown".to_owned()),
+	    volumes: vec![
+		BlockDevice {
+		    device: "/dev/sda1".to_owned(),
+		    volume_id: "vol-bc71579a".to_owned(),
+		    delete_on_termination: true
+		}
 	    ]
 	}
     }
@@ -359,6 +977,444 @@ mod tests {
     }
 
 
+    // EC2 sends its parameters as a form encoded body.
+    fn next_token_of(request: &SignedRequest) -> Option<String> {
+	match &request.payload {
+	    Some(SignedRequestPayload::Buffer(body)) => std::str::from_utf8(body)
+		.unwrap()
+		.split('&')
+		.filter_map(|pair| pair.split_once('='))
+		.find(|(key, _)| *key == "NextToken")
+		.map(|(_, value)| value.to_owned()),
+	    _ => None,
+	}
+    }
+
+
+    fn make_page(instance_id: &str, next_token: Option<&str>) -> String {
+	format!(
+	    r#"<?xml version="1.0" encoding="UTF-8"?><DescribeInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+	    <requestId>1</requestId><reservationSet><item><reservationId>r-1</reservationId><instancesSet>
+	    <item><instanceId>{}</instanceId><instanceState><code>16</code><name>running</name></instanceState></item>
+	    </instancesSet></item></reservationSet>{}</DescribeInstancesResponse>"#,
+	    instance_id,
+	    next_token.map(|token| format!("<nextToken>{}</nextToken>", token)).unwrap_or_default()
+	)
+    }
+
+
+    #[test]
+    fn test_get_instances_by_filters_reads_every_page() {
+	let pages = vec![
+	    MockRequestDispatcher::default()
+		.with_body(&make_page("i-01", Some("page-2")))
+		.with_request_checker(|request| assert_eq!(next_token_of(request), None)),
+	    MockRequestDispatcher::default()
+		.with_body(&make_page("i-02", Some("page-3")))
+		.with_request_checker(|request| assert_eq!(next_token_of(request), Some("page-2".to_owned()))),
+	    MockRequestDispatcher::default()
+		.with_body(&make_page("i-03", None))
+		.with_request_checker(|request| assert_eq!(next_token_of(request), Some("page-3".to_owned()))),
+	];
+	let ec2 = Ec2Client::new_with(
+	    MultipleMockRequestDispatcher::new(pages),
+	    MockCredentialsProvider,
+	    Default::default()
+	);
+	let info = get_instance_info_from_filters(&ec2, vec![("instance-state-name".to_owned(), "running".to_owned())]).unwrap();
+	let ids: Vec<&str> = info.iter().map(|info| info.id.as_str()).collect();
+	assert_eq!(ids, vec!["i-01", "i-02", "i-03"]);
+    }
+
+
+    #[test]
+    fn test_parse_info_of_untagged_and_partial_instances() {
+	let body = r#"<?xml version="1.0" encoding="UTF-8"?><DescribeInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+	    <requestId>1</requestId><reservationSet><item><reservationId>r-1</reservationId><instancesSet>
+	    <item><instanceId>i-0aaaaaaaaaaaaaaaa</instanceId><instanceState><code>16</code><name>running</name></instanceState>
+	    <instanceType>t2.micro</instanceType><privateIpAddress>10.0.0.12</privateIpAddress><ipAddress>54.1.2.3</ipAddress>
+	    <dnsName>ec2-54-1-2-3.us-west-2.compute.amazonaws.com</dnsName><privateDnsName>ip-10-0-0-12</privateDnsName></item>
+	    <item><instanceId>i-0bbbbbbbbbbbbbbbb</instanceId><reason>User initiated</reason><tagSet><item><key>Name</key></item></tagSet></item>
+	    <item><instanceType>t2.micro</instanceType></item>
+	    </instancesSet></item></reservationSet></DescribeInstancesResponse>"#;
+	let ec2 = make_mock_ec2client(body);
+	let info = get_info_from_instances(get_instances_by_filters(&ec2, vec![]).unwrap());
+	assert_eq!(info.len(), 2);
+	assert_eq!(
+	    info[0],
+	    InstanceInfo {
+		size: "t2.micro".to_owned(),
+		id: "i-0aaaaaaaaaaaaaaaa".to_owned(),
+		keyname: None,
+		state: "running".to_owned(),
+		tags: vec![],
+		launch_time: None,
+		private_ip: Some("10.0.0.12".to_owned()),
+		public_ip: Some("54.1.2.3".to_owned()),
+		dns_name: Some("ec2-54-1-2-3.us-west-2.compute.amazonaws.com".to_owned()),
+		availability_zone: None,
+		image_id: None,
+		state_reason: None,
+		volumes: vec![]
+	    }
+	);
+	assert_eq!(info[1].size, UNKNOWN);
+	assert_eq!(info[1].state, UNKNOWN);
+	assert_eq!(info[1].tags, vec![("Name".to_owned(), "".to_owned())]);
+	assert_eq!(info[1].state_reason, Some("User initiated".to_owned()));
+    }
+
+
+    #[test]
+    fn test_get_instance_state_by_id() {
+	let ec2 = make_mock_ec2client(DESCRIBE_INSTANCES_BODY);
+	assert_eq!(get_instance_state_by_id(&ec2, "i-0c3cbd3a6e1b8ffc8").unwrap(), "stopped");
+	let error = get_instance_state_by_id(&ec2, "i-0aaaaaaaaaaaaaaaa").unwrap_err();
+	assert_eq!(error, AwsError::NotFound("i-0aaaaaaaaaaaaaaaa".to_owned()));
+	assert_eq!(error.to_string(), "i-0aaaaaaaaaaaaaaaa did not resolve to any instance");
+	let ec2 = make_mock_ec2client(&DESCRIBE_INSTANCES_BODY.replace("<name>stopped</name>", "<name>terminated</name>"));
+	assert_eq!(get_instance_state_by_id(&ec2, "i-0c3cbd3a6e1b8ffc8").unwrap(), "terminated");
+    }
+
+
+    #[test]
+    fn test_error_from_response() {
+	let body = r#"<?xml version="1.0" encoding="UTF-8"?><Response><Errors><Error><Code>UnauthorizedOperation</Code>
+	    <Message>You are not authorized to perform this operation.</Message></Error></Errors><RequestID>1</RequestID></Response>"#;
+	assert_eq!(
+	    error_from_response(403, body).to_string(),
+	    "The bot is not allowed to do that: You are not authorized to perform this operation."
+	);
+	let body = "<Response><Errors><Error><Code>RequestLimitExceeded</Code><Message>Request limit exceeded.</Message></Error></Errors></Response>";
+	assert_eq!(error_from_response(503, body), AwsError::Throttled);
+	let body = "<Response><Errors><Error><Code>IncorrectInstanceState</Code><Message>The instance is not in a state from which it can be started.</Message></Error></Errors></Response>";
+	assert_eq!(
+	    error_from_response(400, body),
+	    AwsError::Rejected {
+		code: "IncorrectInstanceState".to_owned(),
+		message: "The instance is not in a state from which it can be started.".to_owned()
+	    }
+	);
+	assert_eq!(error_from_response(403, ""), AwsError::PermissionDenied("HTTP 403".to_owned()));
+	assert_eq!(error_from_response(502, ""), AwsError::Transport("HTTP 502".to_owned()));
+    }
+
+
+    #[test]
+    fn test_errors_from_ec2_responses() {
+	let body = "<Response><Errors><Error><Code>AuthFailure</Code><Message>AWS was not able to validate the provided access credentials</Message></Error></Errors></Response>";
+	let mock = MockRequestDispatcher::with_status(401).with_body(body);
+	let ec2 = Ec2Client::new_with(mock, MockCredentialsProvider, Default::default());
+	let url = "https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/".to_string();
+	assert_eq!(
+	    resolve_instance(&ec2, &url).unwrap_err(),
+	    AwsError::PermissionDenied("AWS was not able to validate the provided access credentials".to_owned())
+	);
+	let body = "<Response><Errors><Error><Code>InvalidParameterValue</Code><Message>Value (c5.huge) for parameter instanceType is invalid.</Message></Error></Errors></Response>";
+	let mock = MockRequestDispatcher::with_status(400).with_body(body);
+	let ec2 = Ec2Client::new_with(mock, MockCredentialsProvider, Default::default());
+	assert_eq!(
+	    resize_instance_by_id(&ec2, "i-0c3cbd3a6e1b8ffc8".to_owned(), "c5.huge".to_owned()).unwrap_err(),
+	    AwsError::InvalidInstanceType("c5.huge".to_owned())
+	);
+	let ec2 = make_mock_ec2client(DESCRIBE_INSTANCES_BODY);
+	let resolved = resolve_instance(&ec2, &url).unwrap();
+	assert_eq!(
+	    resize_instance(&ec2, &resolved, "c5.huge".to_owned()).unwrap_err().to_string(),
+	    "c5.huge is not a valid instance type"
+	);
+    }
+
+
+    // DESCRIBE_INSTANCES_BODY with a second copy of its instance as `id` in `state`.
+    fn with_second_instance(id: &str, state: &str) -> String {
+	let start = DESCRIBE_INSTANCES_BODY.find("<instancesSet>").unwrap() + "<instancesSet>".len();
+	let end = DESCRIBE_INSTANCES_BODY.find("</instancesSet>").unwrap();
+	let copy = DESCRIBE_INSTANCES_BODY[start..end]
+	    .replace("i-0c3cbd3a6e1b8ffc8", id)
+	    .replace("<name>stopped</name>", &format!("<name>{}</name>", state));
+	format!("{}{}{}", &DESCRIBE_INSTANCES_BODY[..start], copy, &DESCRIBE_INSTANCES_BODY[start..])
+    }
+
+
+    #[test]
+    fn test_identifier_parse() {
+	let cases = vec![
+	    ("https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/", Identifier::Url("encd-5328-3a048a0ae-emma".to_owned())),
+	    ("i-02e86c27e5d31f8d1", Identifier::InstanceId("i-02e86c27e5d31f8d1".to_owned())),
+	    ("@emma", Identifier::User("emma".to_owned())),
+	    ("ENCD-5328", Identifier::Ticket("ENCD-5328".to_owned())),
+	    ("encd-5328", Identifier::Ticket("ENCD-5328".to_owned())),
+	    ("3a048a0ae", Identifier::Commit("3a048a0ae".to_owned())),
+	    ("encd-5328-3a048a0ae-emma", Identifier::DemoName("encd-5328-3a048a0ae-emma".to_owned())),
+	    ("v102rc2", Identifier::DemoName("v102rc2".to_owned())),
+	];
+	for (value, expected) in cases {
+	    assert_eq!(Identifier::parse(value), Some(expected), "{}", value);
+	}
+	assert_eq!(Identifier::parse("not a url"), None);
+	assert_eq!(Identifier::parse("3a04"), None);
+	assert_eq!(Identifier::parse("@"), None);
+	assert_eq!(Identifier::parse("test-123"), Some(Identifier::Ticket("TEST-123".to_owned())));
+    }
+
+
+    #[test]
+    fn test_resolve_by_each_rule() {
+	let ec2 = make_mock_ec2client(DESCRIBE_INSTANCES_BODY);
+	let cases = vec![
+	    ("https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/", "demo URL"),
+	    ("i-0c3cbd3a6e1b8ffc8", "instance ID"),
+	    ("@emma", "started_by tag"),
+	    ("ENCD-5328", "Jira ticket in the branch tag"),
+	    ("3a048a0ae", "commit hash prefix"),
+	    ("3a048a0ae5b1c2d3e4f5", "commit hash prefix"),
+	    ("encd-5328-3a048a0ae-emma", "demo name"),
+	];
+	for (value, rule) in cases {
+	    let resolved = resolve_instance(&ec2, value).unwrap();
+	    assert_eq!(resolved.instance, make_expected_instance_info(), "{}", value);
+	    assert_eq!(resolved.identifier.rule(), rule);
+	}
+	for value in &["@keenan", "ENCD-532", "3a048a0af", "encd-5328-3a048a0ae"] {
+	    assert_eq!(resolve_instance(&ec2, value).unwrap_err(), AwsError::NotFound(value.to_string()));
+	}
+	assert_eq!(
+	    resolve_instance(&ec2, "ENCD-5328").unwrap().describe(),
+	    "i-0c3cbd3a6e1b8ffc8 (stopped, c5.9xlarge, encd-5328-3a048a0ae-emma), matched by Jira ticket in the branch tag"
+	);
+    }
+
+
+    #[test]
+    fn test_resolve_ticket_before_demo_name() {
+	let ec2 = make_mock_ec2client(&DESCRIBE_INSTANCES_BODY.replace("encd-5328-3a048a0ae-emma", "encd-5328"));
+	assert_eq!(resolve_instance(&ec2, "encd-5328").unwrap().identifier, Identifier::Ticket("ENCD-5328".to_owned()));
+	let ec2 = make_mock_ec2client(&DESCRIBE_INSTANCES_BODY.replace("encd-5328-3a048a0ae-emma", "test-123"));
+	assert_eq!(resolve_instance(&ec2, "test-123").unwrap().identifier, Identifier::DemoName("test-123".to_owned()));
+	assert_eq!(resolve_instance(&ec2, "TEST-123").unwrap_err(), AwsError::NotFound("TEST-123".to_owned()));
+    }
+
+
+    #[test]
+    fn test_resolve() {
+	let url = "https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/";
+	let ec2 = make_mock_ec2client(DESCRIBE_INSTANCES_BODY);
+	let (_, resolution) = resolve(&ec2, url).unwrap();
+	assert_eq!(resolution, Resolution::One(Box::new(make_expected_instance_info())));
+	assert_eq!(resolve(&ec2, "not a url").unwrap(), (None, Resolution::NoMatch));
+	let ec2 = make_mock_ec2client(&with_second_instance("i-0aaaaaaaaaaaaaaaa", "terminated"));
+	assert_eq!(resolve_instance(&ec2, url).unwrap().instance, make_expected_instance_info());
+	let ec2 = make_mock_ec2client(&with_second_instance("i-0aaaaaaaaaaaaaaaa", "running"));
+	match resolve(&ec2, url).unwrap().1 {
+	    Resolution::Many(matches) => assert_eq!(matches.len(), 2),
+	    resolution => panic!("Expected several matches, got {:?}", resolution),
+	}
+    }
+
+
+    #[test]
+    fn test_ambiguous_url() {
+	let ec2 = make_mock_ec2client(&with_second_instance("i-0aaaaaaaaaaaaaaaa", "running"));
+	let url = "https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/".to_string();
+	let error = resolve_instance(&ec2, &url).unwrap_err();
+	assert_eq!(
+	    error.to_string(),
+	    format!(
+		"{} matches 2 instances, use one of their IDs instead: {}, {}",
+		url,
+		"i-0aaaaaaaaaaaaaaaa (running, c5.9xlarge, encd-5328-3a048a0ae-emma)",
+		"i-0c3cbd3a6e1b8ffc8 (stopped, c5.9xlarge, encd-5328-3a048a0ae-emma)"
+	    )
+	);
+	let error = resolve_instance(&ec2, "@emma").unwrap_err();
+	assert!(matches!(error, AwsError::Ambiguous(..)));
+    }
+
+
+    #[test]
+    fn test_parse_accounts_and_regions() {
+	let accounts = parse_accounts("sandbox=arn:aws:iam::111:role/rustybot, production = arn:aws:iam::222:role/rustybot,broken");
+	assert_eq!(accounts.len(), 2);
+	assert_eq!(accounts[1].name, "production");
+	assert_eq!(accounts[1].role_arn, "arn:aws:iam::222:role/rustybot");
+	assert_eq!(parse_regions("us-west-2, us-east-1").unwrap(), vec![Region::UsWest2, Region::UsEast1]);
+	assert_eq!(parse_regions("us-west-2,mars-1").unwrap_err(), "Unknown region `mars-1`");
+	assert!(parse_regions(" ,").is_err());
+    }
+
+
+    #[test]
+    fn test_config_accounts_and_target_labels() {
+	let config = AwsConfig {
+	    regions: vec![Region::UsEast1, Region::UsWest2],
+	    accounts: parse_accounts("sandbox=arn:aws:iam::111:role/rustybot"),
+	    protected_tags: vec![]
+	};
+	assert_eq!(config.default_region(), Region::UsEast1);
+	assert_eq!(
+	    config.account("prod").unwrap_err(),
+	    "Unknown account `prod`, expected one of sandbox"
+	);
+	let target = Target {
+	    region: config.default_region(),
+	    account: Some(config.account("sandbox").unwrap()),
+	    ..Default::default()
+	};
+	assert_eq!(target.label(), "sandbox/us-east-1");
+	let target = Target {
+	    profile: Some("encode".to_owned()),
+	    ..Default::default()
+	};
+	assert_eq!(target.label(), "encode/us-west-2");
+	assert_eq!(Target::default().label(), "default/us-west-2");
+    }
+
+
+    #[test]
+    fn test_demo_url() {
+	let mut info = make_expected_instance_info();
+	assert_eq!(info.demo_url(), Some("https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/".to_owned()));
+	info.tags = vec![("Name".to_owned(), "Build box".to_owned())];
+	assert_eq!(info.demo_url(), None);
+	info.tags = vec![];
+	assert_eq!(info.demo_url(), None);
+    }
+
+
+    #[test]
+    fn test_protected_by() {
+	let protected_tags = parse_protected_tags("protected, started_by=emma,");
+	assert_eq!(protected_tags, vec![("protected".to_owned(), None), ("started_by".to_owned(), Some("emma".to_owned()))]);
+	let info = make_expected_instance_info();
+	assert_eq!(info.protected_by(&protected_tags), Some("started_by=emma".to_owned()));
+	assert_eq!(info.protected_by(&parse_protected_tags("notebook")), Some("notebook=123".to_owned()));
+	assert_eq!(info.protected_by(&parse_protected_tags("protected,started_by=keenan")), None);
+    }
+
+
+    #[test]
+    fn test_has_termination_protection() {
+	let body = r#"<?xml version="1.0" encoding="UTF-8"?><DescribeInstanceAttributeResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+	    <requestId>1</requestId><instanceId>i-0c3cbd3a6e1b8ffc8</instanceId><disableApiTermination><value>true</value></disableApiTermination>
+	    </DescribeInstanceAttributeResponse>"#;
+	let resolved = Resolved {
+	    identifier: Identifier::InstanceId("i-0c3cbd3a6e1b8ffc8".to_owned()),
+	    instance: make_expected_instance_info()
+	};
+	let mock = MockRequestDispatcher::default()
+	    .with_body(body)
+	    .with_request_checker(|request| {
+		let body = match &request.payload {
+		    Some(SignedRequestPayload::Buffer(body)) => std::str::from_utf8(body).unwrap().to_owned(),
+		    _ => String::new(),
+		};
+		assert!(body.contains("Attribute=disableApiTermination"));
+	    });
+	let ec2 = Ec2Client::new_with(mock, MockCredentialsProvider, Default::default());
+	assert!(has_termination_protection(&ec2, &resolved).unwrap());
+	let ec2 = make_mock_ec2client(&body.replace("true", "false"));
+	assert!(!has_termination_protection(&ec2, &resolved).unwrap());
+    }
+
+
+    fn with_state(state: &str) -> MockRequestDispatcher {
+	MockRequestDispatcher::default()
+	    .with_body(&DESCRIBE_INSTANCES_BODY.replace("<name>stopped</name>", &format!("<name>{}</name>", state)))
+    }
+
+
+    #[test]
+    fn test_wait_for_state_reports_transitions() {
+	let ec2 = Ec2Client::new_with(
+	    MultipleMockRequestDispatcher::new(vec![with_state("running"), with_state("stopping"), with_state("stopping"), with_state("stopped")]),
+	    MockCredentialsProvider,
+	    Default::default()
+	);
+	let mut sleeps = 0;
+	let mut transitions = vec![];
+	let result = wait_for_state(
+	    &ec2,
+	    "i-0c3cbd3a6e1b8ffc8",
+	    "stopped",
+	    Duration::minutes(5),
+	    |_| { sleeps += 1; true },
+	    |state, _| transitions.push(state.to_owned())
+	);
+	assert!(result.is_ok());
+	assert_eq!(sleeps, 3);
+	assert_eq!(transitions, vec!["running", "stopping", "stopped"]);
+    }
+
+
+    #[test]
+    fn test_wait_for_state_gives_up() {
+	let ec2 = make_mock_ec2client(&DESCRIBE_INSTANCES_BODY.replace("<name>stopped</name>", "<name>running</name>"));
+	let result = wait_for_state(&ec2, "i-0c3cbd3a6e1b8ffc8", "stopped", Duration::zero(), |_| true, |_, _| {});
+	assert_eq!(result, Err(AwsError::TimedOut {
+	    instance_id: "i-0c3cbd3a6e1b8ffc8".to_owned(),
+	    state: "stopped".to_owned(),
+	    last_state: "running".to_owned()
+	}));
+	let result = wait_for_state(&ec2, "i-0c3cbd3a6e1b8ffc8", "stopped", Duration::minutes(5), |_| false, |_, _| {});
+	assert_eq!(result, Err(AwsError::Cancelled));
+	let ec2 = make_mock_ec2client(&DESCRIBE_INSTANCES_BODY.replace("<name>stopped</name>", "<name>shutting-down</name>"));
+	let result = wait_for_state(&ec2, "i-0c3cbd3a6e1b8ffc8", "stopped", Duration::minutes(5), |_| true, |_, _| {});
+	assert_eq!(result, Err(AwsError::UnexpectedState {
+	    instance_id: "i-0c3cbd3a6e1b8ffc8".to_owned(),
+	    state: "shutting-down".to_owned()
+	}));
+    }
+
+
+    #[test]
+    fn test_wait_for_state_sees_termination() {
+	let ec2 = Ec2Client::new_with(
+	    MultipleMockRequestDispatcher::new(vec![with_state("stopping"), with_state("terminated")]),
+	    MockCredentialsProvider,
+	    Default::default()
+	);
+	let mut transitions = vec![];
+	let result = wait_for_state(
+	    &ec2,
+	    "i-0c3cbd3a6e1b8ffc8",
+	    "stopped",
+	    Duration::minutes(5),
+	    |_| true,
+	    |state, _| transitions.push(state.to_owned())
+	);
+	assert_eq!(result, Err(AwsError::UnexpectedState {
+	    instance_id: "i-0c3cbd3a6e1b8ffc8".to_owned(),
+	    state: "terminated".to_owned()
+	}));
+	assert_eq!(transitions, vec!["stopping", "terminated"]);
+    }
+
+
+    #[test]
+    fn test_reboot_instance() {
+	let body = r#"<?xml version="1.0" encoding="UTF-8"?><RebootInstancesResponse xmlns="http://ec2.amazonaws.com/doc/2016-11-15/">
+	    <requestId>1</requestId><return>true</return>
+	    </RebootInstancesResponse>"#;
+	let resolved = Resolved {
+	    identifier: Identifier::InstanceId("i-0c3cbd3a6e1b8ffc8".to_owned()),
+	    instance: make_expected_instance_info()
+	};
+	let mock = MockRequestDispatcher::default()
+	    .with_body(body)
+	    .with_request_checker(|request| {
+		let body = match &request.payload {
+		    Some(SignedRequestPayload::Buffer(body)) => std::str::from_utf8(body).unwrap().to_owned(),
+		    _ => String::new(),
+		};
+		assert!(body.contains("Action=RebootInstances"));
+		assert!(body.contains("InstanceId.1=i-0c3cbd3a6e1b8ffc8"));
+	    });
+	let ec2 = Ec2Client::new_with(mock, MockCredentialsProvider, Default::default());
+	assert_eq!(reboot_instance(&ec2, &resolved), Ok(()));
+    }
+
+
     #[test]
     fn test_parse_name_from_url() {
 	let url = "https://v102rc2.demo.encodedcc.org".to_string();
@@ -376,24 +1432,26 @@ mod tests {
     #[test]
     fn test_is_instance_id() {
 	let id = "i-02e86c27e5d31f8d1".to_string();
-	assert_eq!(true, is_instance_id(id));
+	assert!(is_instance_id(id));
 	let id = "i-02e86c27e5d311".to_string();
-	assert_eq!(true, is_instance_id(id));
+	assert!(is_instance_id(id));
 	let id = "encd-5358-d5b93454a-emma".to_string();
-	assert_eq!(false, is_instance_id(id));
+	assert!(!is_instance_id(id));
 	let id = "http:i-02e86c27e5d31f8d1".to_string();
-	assert_eq!(false, is_instance_id(id));
+	assert!(!is_instance_id(id));
     }
 
 
     #[test]
-    fn test_get_instance_ids_from_url_or_id() {
-	let ec2 = make_mock_ec2client(DESCRIBE_INSTANCES_BODY);
-        let url = "https://encd-5358-d5b93454a-emma.demo.encodedcc.org/".to_string();
-	let instance_ids = get_instance_ids_from_url_or_id(&ec2, url);
-	assert_eq!(instance_ids, vec!["i-0c3cbd3a6e1b8ffc8".to_owned()]);
-	let id = "i-0c3cbd3a6e1b8ffc7".to_string();
-	let instance_ids = get_instance_ids_from_url_or_id(&ec2, id);
-	assert_eq!(instance_ids, vec!["i-0c3cbd3a6e1b8ffc7".to_owned()]);
+    fn test_is_valid_instance_type() {
+	assert!(is_valid_instance_type("r5.2xlarge"));
+	assert!(is_valid_instance_type("c5.9xlarge"));
+	assert!(is_valid_instance_type("t2.micro"));
+	assert!(is_valid_instance_type("m5d.large"));
+	assert!(is_valid_instance_type("c5n.metal"));
+	assert!(is_valid_instance_type("u-6tb1.metal"));
+	assert!(!is_valid_instance_type("c5.huge"));
+	assert!(!is_valid_instance_type("r5"));
+	assert!(!is_valid_instance_type("--size"));
     }
 }
@@ -1,90 +1,346 @@
+use crate::audit::{Audit, AuditEntry};
+use crate::aws;
 use crate::constants;
-use crate::aws::start_instance_by_url_or_id;
-use crate::aws::stop_instance_by_url_or_id;
-use crate::aws::resize_instance_by_url_or_id;
-use crate::aws::make_ec2_client;
-use crate::aws::get_instance_info_from_url_or_id;
-use crate::aws::get_instance_info_from_filters;
-use serde::Deserialize;
-use rand::seq::{SliceRandom};
+use crate::registry::Registry;
+use crate::history::{self, History};
+use crate::http::{self, HttpOptions};
+use crate::monitor::{IndexerEndpoint, MonitorOptions, OnError, ProblemTracker};
+use crate::progress::{self, Estimate, Progress, Sample};
+use crate::tokenize::tokenize;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::{thread, time};
 use std::error::Error;
+use std::fmt;
 use slack_api::{self, MessageStandard};
 use slack_api::chat::PostMessageRequest;
 use crossbeam_channel::Sender;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use clap::{Arg, App, ArgMatches, Values};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use clap::ArgMatches;
 use regex::Regex;
-use lazy_static;
 use reqwest::blocking::Client;
 
 
-pub struct RustyBot<'a> {
-    emojis: Vec<&'a str>,
-    id: &'a str,
+pub struct RustyBot {
     token: String,
     worker_id: String,
     tx: Sender<String>,
-    is_cancelled: Arc<AtomicBool>
+    is_cancelled: Arc<AtomicBool>,
+    // Set when one of several indexers polled in parallel fails, so the
+    // others stop waiting for an outcome that is already decided.
+    sibling_failed: AtomicBool,
+    registry: Arc<Registry>,
+    history: Arc<History>,
+    audit: Arc<Audit>
 }
 
 
-#[derive(Debug, Deserialize)]
+// Mirrors the JSON served by encoded's `/_indexer` and `/_visindexer`. Every
+// field except `status` is optional and unknown fields are kept in `extra`,
+// so older or newer deployments still deserialize.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct IndexerResult {
-    cycle_took: String
+    pub status: Option<String>,
+    pub xmin: Option<u64>,
+    pub last_xmin: Option<u64>,
+    pub max_xid: Option<u64>,
+    pub invalidated: Option<u64>,
+    pub indexed: Option<u64>,
+    pub updated: Option<u64>,
+    pub renamed: Option<u64>,
+    pub txn_count: Option<u64>,
+    pub first_txn_timestamp: Option<String>,
+    pub cycle_started: Option<String>,
+    pub cycle_took: Option<String>,
+    pub indexing_elapsed: Option<String>,
+    pub errors: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>
 }
 
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct IndexerListener {
+    pub status: Option<String>,
+    pub started: Option<String>,
+    pub timestamp: Option<String>,
+    pub last_result: Option<Value>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Indexer {
-    status: String,
-    results: Vec<IndexerResult>
+    pub status: String,
+    pub title: Option<String>,
+    pub docs_in_index: Option<u64>,
+    pub uuids_in_progress: Option<u64>,
+    pub uuids_troubled: Option<u64>,
+    pub uuids_last_run: Option<u64>,
+    pub now: Option<String>,
+    pub listener: Option<IndexerListener>,
+    pub state: Option<IndexerResult>,
+    #[serde(default)]
+    pub results: Vec<IndexerResult>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>
+}
+
+
+// Parses Python's `str(timedelta)`, e.g. `0:01:23.456789` or `1 day, 2:03:04`.
+pub fn parse_cycle_took(value: &str) -> Option<Duration> {
+    lazy_static! {
+        static ref TIMEDELTA_RE: Regex = Regex::new(
+	    r"^(?:(\d+) days?, )?(\d+):(\d{2}):(\d{2})(?:\.(\d{1,6}))?$"
+	).unwrap();
+    }
+    let capture = TIMEDELTA_RE.captures(value.trim())?;
+    let number = |i: usize| capture.get(i).map_or(Some(0), |x| x.as_str().parse::<i64>().ok());
+    let micros = capture.get(5).map_or(Some(0), |x| format!("{:0<6}", x.as_str()).parse::<i64>().ok())?;
+    Duration::try_days(number(1)?)?
+	.checked_add(&Duration::try_hours(number(2)?)?)?
+	.checked_add(&Duration::try_minutes(number(3)?)?)?
+	.checked_add(&Duration::try_seconds(number(4)?)?)?
+	.checked_add(&Duration::microseconds(micros))
+}
+
+
+// Returns the user ID of a Slack mention, which the tokenizer leaves as
+// `@U0123ABC`, as opposed to a plain `@name`.
+pub fn parse_mention_id(value: &str) -> Option<&str> {
+    lazy_static! {
+        static ref MENTION_RE: Regex = Regex::new(r"^@([UW][0-9A-Z]{6,})$").unwrap();
+    }
+    MENTION_RE.captures(value.trim()).map(|capture| capture.get(1).unwrap().as_str())
+}
+
+
+pub fn parse_cycle_started(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(started) = DateTime::parse_from_rfc3339(value) {
+	return Some(started.naive_utc());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").ok()
 }
 
 
-#[tokio::main]
-pub async fn get_indexer_results(url: &str) -> Result<Indexer, reqwest::Error> {
-    let indexer = format!("{}/_indexer", url);
-    let json = reqwest::get(&indexer)
-        .await?
-	.json()
-	.await?;
-    Ok(json)
+pub fn describe_indexer_error(error: &Value) -> String {
+    match error {
+	Value::String(error) => error.to_owned(),
+	Value::Object(error) => {
+	    let uuid = error.get("uuid").and_then(|x| x.as_str()).unwrap_or("unknown uuid");
+	    let message = error.get("error_message")
+		.or_else(|| error.get("message"))
+		.and_then(|x| x.as_str())
+		.map(|x| x.to_owned())
+		.unwrap_or_else(|| Value::Object(error.clone()).to_string());
+	    format!("{}: {}", uuid, message)
+	},
+	_ => error.to_string(),
+    }
 }
 
 
-#[tokio::main]
-pub async fn get_visindexer_results(url: &str) -> Result<Indexer, reqwest::Error> {
-    let indexer = format!("{}/_visindexer", url);
-    let json = reqwest::get(&indexer)
-        .await?
-	.json()
-	.await?;
-    Ok(json)
+impl IndexerResult {
+    pub fn errors(&self) -> &[Value] {
+	self.errors.as_deref().unwrap_or(&[])
+    }
+
+    pub fn cycle_finished(&self) -> Option<NaiveDateTime> {
+	let started = parse_cycle_started(self.cycle_started.as_ref()?)?;
+	started.checked_add_signed(parse_cycle_took(self.cycle_took.as_ref()?)?)
+    }
+
+    pub fn describe_cycle(&self) -> String {
+	let mut parts = vec![];
+	if let Some(started) = &self.cycle_started {
+	    parts.push(format!("started {}", started));
+	}
+	if let Some(finished) = self.cycle_finished() {
+	    parts.push(format!("finished {}", finished.format("%Y-%m-%dT%H:%M:%S")));
+	}
+	if let Some(took) = &self.cycle_took {
+	    parts.push(format!("took {}", took));
+	}
+	match (self.indexed, self.invalidated) {
+	    (Some(indexed), Some(invalidated)) => parts.push(format!("indexed {} of {} invalidated", indexed, invalidated)),
+	    (Some(indexed), None) => parts.push(format!("indexed {}", indexed)),
+	    (None, Some(invalidated)) => parts.push(format!("{} invalidated", invalidated)),
+	    (None, None) => (),
+	}
+	if !self.errors().is_empty() {
+	    parts.push(format!("errors: {}", self.errors().len()));
+	}
+	parts.join(", ")
+    }
 }
 
 
-impl<'a> RustyBot<'a> {
-    pub fn new(token: String, worker_id: String, tx: Sender<String>, is_cancelled: Arc<AtomicBool>) -> Self {
+impl Indexer {
+    pub fn errors(&self) -> Vec<&Value> {
+	let mut errors = vec![];
+	if let Some(state) = &self.state {
+	    errors.extend(state.errors());
+	}
+	if let Some(result) = self.results.first() {
+	    errors.extend(result.errors());
+	}
+	errors.dedup();
+	errors
+    }
+
+    pub fn uuids_being_indexed(&self) -> Option<u64> {
+	match self.uuids_in_progress {
+	    Some(uuids) if uuids > 0 => Some(uuids),
+	    _ if self.status == "indexing" => self.state.as_ref().and_then(|state| state.invalidated),
+	    uuids => uuids,
+	}
+    }
+
+    pub fn sample(&self, at: DateTime<Utc>) -> Option<Sample> {
+	Some(Sample {
+	    at,
+	    queued: self.uuids_being_indexed()?,
+	    indexed: self.state.as_ref().and_then(|state| state.indexed)
+	})
+    }
+
+    // Estimates the rest of the current cycle from how much it indexed so far.
+    pub fn cycle_estimate(&self) -> Option<Estimate> {
+	if self.status != "indexing" {
+	    return None;
+	}
+	let state = self.state.as_ref()?;
+	let elapsed = match &state.indexing_elapsed {
+	    Some(elapsed) => parse_cycle_took(elapsed)?,
+	    None => {
+		let now = NaiveDateTime::parse_from_str(self.now.as_ref()?, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+		now - parse_cycle_started(state.cycle_started.as_ref()?)?
+	    },
+	};
+	progress::estimate_from_cycle(self.uuids_being_indexed()?, state.indexed?, elapsed)
+    }
+
+    // Key values used to compare deployments side by side.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+	let show = |value: Option<u64>| value.map(|x| x.to_string()).unwrap_or_else(|| "-".to_owned());
+	let last = self.results.first();
+	vec![
+	    ("Status", self.status.clone()),
+	    ("Docs in index", show(self.docs_in_index)),
+	    ("UUIDs being indexed", show(self.uuids_being_indexed())),
+	    ("UUIDs troubled", show(self.uuids_troubled)),
+	    ("Last cycle took", last.and_then(|x| x.cycle_took.clone()).unwrap_or_else(|| "-".to_owned())),
+	    ("Last cycle indexed", show(last.and_then(|x| x.indexed))),
+	    ("Last cycle invalidated", show(last.and_then(|x| x.invalidated))),
+	    ("Errors", self.errors().len().to_string()),
+	]
+    }
+
+    pub fn summary(&self) -> String {
+	let mut lines = vec![format!("Status: {}", self.status)];
+	if let Some(uuids) = self.uuids_being_indexed() {
+	    lines.push(format!("UUIDs being indexed: {}", uuids));
+	}
+	if let Some(docs) = self.docs_in_index {
+	    lines.push(format!("Docs in index: {}", docs));
+	}
+	if let Some(state) = &self.state {
+	    if self.status == "indexing" && state.cycle_started.is_some() {
+		lines.push(format!("Current cycle: {}", state.describe_cycle()));
+	    }
+	}
+	if let Some(estimate) = self.cycle_estimate() {
+	    lines.push(format!("Progress: {}", estimate.describe()));
+	}
+	if let Some(result) = self.results.first() {
+	    lines.push(format!("Last cycle: {}", result.describe_cycle()));
+	}
+	if let Some(listener) = &self.listener {
+	    if let Some(status) = &listener.status {
+		lines.push(format!("Listener: {}", status));
+	    }
+	}
+	let errors = self.errors();
+	if !errors.is_empty() {
+	    lines.push(format!("Errors ({}):", errors.len()));
+	    for error in errors.iter().take(constants::MAX_INDEXER_ERRORS) {
+		lines.push(format!("    {}", describe_indexer_error(error)));
+	    }
+	}
+	lines.join("\n")
+    }
+}
+
+
+pub fn get_indexer(url: &str, path: &str, options: &HttpOptions) -> Result<Indexer, Box<dyn Error>> {
+    Ok(serde_json::from_value(http::get_json(url, path, options)?)?)
+}
+
+
+impl RustyBot {
+    pub fn new(token: String, worker_id: String, tx: Sender<String>, is_cancelled: Arc<AtomicBool>, registry: Arc<Registry>, history: Arc<History>, audit: Arc<Audit>) -> Self {
         RustyBot {
-	    emojis: constants::EMOJIS.to_vec(),
-	    id: constants::BOT_ID,
-	    token: token,
-	    worker_id: worker_id,
-	    tx: tx,
-	    is_cancelled: is_cancelled
+	    token,
+	    worker_id,
+	    tx,
+	    is_cancelled,
+	    sibling_failed: AtomicBool::new(false),
+	    registry,
+	    history,
+	    audit
 	}
     }
 
+    pub fn registry(&self) -> &Registry {
+	&self.registry
+    }
+
+    pub fn history(&self) -> &History {
+	&self.history
+    }
+
+    // Records `action` on `subject`, requested by the author of `message`.
+    pub fn audit(&self, message: &MessageStandard, action: &str, subject: &str, outcome: &str) {
+	self.audit.record(AuditEntry {
+	    at: Utc::now(),
+	    user: message.user.clone(),
+	    channel: message.channel.clone(),
+	    action: action.to_owned(),
+	    subject: subject.to_owned(),
+	    outcome: outcome.to_owned()
+	});
+    }
+
+    pub fn record_cycles(&self, parsed_url: &str, indexer: &IndexerEndpoint, response: &Indexer) {
+	self.history.record(history::records_from_indexer(parsed_url, indexer.name, response, Utc::now()));
+    }
+
     fn get_client(&self) -> Client {
 	slack_api::requests::default_client().unwrap()
     }
 
-    fn get_message(&self, channel: &'a str, text: &'a str) -> PostMessageRequest<'a> {
+    // Instances are tagged with the Slack user name, not the user ID.
+    fn get_user_name(&self, user_id: &str) -> Option<String> {
+	let request = slack_api::users::InfoRequest { user: user_id };
+	match slack_api::users::info(&self.get_client(), &self.token, &request) {
+	    Ok(response) => response.user.and_then(|user| user.name),
+	    Err(error) => {
+		println!("Error looking up Slack user {}: {}", user_id, error);
+		None
+	    },
+	}
+    }
+
+    fn get_message<'m>(&self, channel: &'m str, text: &'m str, thread_ts: Option<&'m str>) -> PostMessageRequest<'m> {
         PostMessageRequest {
-	    channel: &channel,
-	    text: &text,
+	    channel,
+	    text,
+	    thread_ts,
 	    as_user: Some(true),
 	    ..Default::default()
 	}
@@ -97,40 +353,72 @@ impl<'a> RustyBot<'a> {
 	text.to_owned()
     }
 
-    fn get_url_value_and_parse(&self, matches: &ArgMatches) -> Option<String> {
-	if let Some(url) = matches.value_of("url") {
-	     return self.maybe_parse_slack_url(url)
-	}
-	None
+    pub fn get_url_value_and_parse(&self, matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
+	let url = matches.value_of("url").ok_or("Missing URL")?;
+	self.maybe_parse_slack_url(url)
+	    .ok_or_else(|| format!("Could not parse a URL from `{}`", url).into())
+    }
+
+    pub fn get_url_values_and_parse(&self, matches: &ArgMatches) -> Result<Vec<String>, Box<dyn Error>> {
+	let urls = matches.values_of("url").ok_or("Missing URL")?;
+	urls.map(|url| {
+	    self.maybe_parse_slack_url(url)
+		.ok_or_else(|| format!("Could not parse a URL from `{}`", url).into())
+	}).collect()
     }
 
-    fn get_url_or_id_value_and_parse(&self, matches: &ArgMatches) -> Option<String> {
+    pub fn get_url_or_id_value_and_parse(&self, matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
 	if let Some(url_or_id) = matches.value_of("url_or_id") {
-	     return self.maybe_parse_slack_url_or_id(url_or_id)
+	    if let Some(user_id) = parse_mention_id(url_or_id) {
+		let name = self.get_user_name(user_id)
+		    .ok_or_else(|| format!("Could not look up the Slack user `{}`", url_or_id))?;
+		return Ok(format!("@{}", name));
+	    }
+	    return self.maybe_parse_slack_url_or_id(url_or_id)
+		.ok_or_else(|| format!("Could not parse an instance from `{}`, expected a URL, instance ID, demo name, Jira ticket, commit hash prefix or @user", url_or_id).into());
+	}
+	if matches.value_of("url").is_some() {
+	    return self.get_url_value_and_parse(matches);
 	}
-	self.get_url_value_and_parse(matches)
+	Err("Missing URL or instance ID".into())
     }
 
-    fn say(&self, channel: &Option<String>, text: &str, add_job_id: bool) {
-        slack_api::chat::post_message(
+    fn post(&self, channel: &Option<String>, text: &str, add_job_id: bool, thread_ts: Option<&str>) {
+        let _ = slack_api::chat::post_message(
 	    &self.get_client(),
 	    &self.token,
 	    &self.get_message(
-		&self.unwrap_string(channel),
+		self.unwrap_string(channel),
 	        &self.format_text(
 		    &text.chars().take(constants::MAX_MESSAGE_SIZE).collect::<String>(),
 		    add_job_id
-		)
+		),
+		thread_ts
 	    )
 	);
     }
 
-    fn get_random_emoji(&self) -> &'a str{
-        self.emojis.choose(&mut rand::thread_rng()).unwrap()
+    pub fn say(&self, channel: &Option<String>, text: &str, add_job_id: bool) {
+	self.post(channel, text, add_job_id, None);
     }
 
-    fn unwrap_string(&self, string: &'a Option<String>) -> &'a String {
-        &string.as_ref().unwrap()
+    // Replies in the thread of `message`, starting one if it is not already in a thread.
+    pub fn say_in_thread(&self, message: &MessageStandard, text: &str, add_job_id: bool) {
+	let thread_ts = message.thread_ts.as_ref().or(message.ts.as_ref());
+	self.post(&message.channel, text, add_job_id, thread_ts.map(|x| x.as_str()));
+    }
+
+    // Replies with the error and the usage line of the command at `path`.
+    pub fn say_usage_error(&self, channel: &Option<String>, path: &[&str], error: &dyn fmt::Display) {
+	let value = match self.registry.usage(path) {
+	    Some(usage) => format!("{}\nUsage: {}", error, usage),
+	    None => error.to_string(),
+	};
+	self.say(channel, &value, true);
+    }
+
+    fn unwrap_string<'s>(&self, string: &'s Option<String>) -> &'s String {
+        string.as_ref().unwrap()
     }
 
     fn maybe_parse_slack_url(&self, url: &str) -> Option<String> {
@@ -147,407 +435,398 @@ impl<'a> RustyBot<'a> {
 	lazy_static! {
             static ref ID_RE: Regex = Regex::new(r"^i-[0-9][0-9a-zA-Z]*").unwrap();
 	}
-	if let Some(url) = self.maybe_parse_slack_url(&url_or_id) {
+	if let Some(url) = self.maybe_parse_slack_url(url_or_id) {
 	    return Some(url)
 	}
 	if let Some(id) = ID_RE.captures(url_or_id) {
 	    return Some(id.get(0).unwrap().as_str().to_owned());
 	}
-	None
+	aws::Identifier::parse(url_or_id).map(|_| url_or_id.trim().to_owned())
     }
 
-    fn should_stop(&self) -> bool {
+    pub fn should_stop(&self) -> bool {
 	if self.is_cancelled.load(Ordering::Relaxed) {
 	    return true;
 	}
 	false
     }
 
-    fn make_app(&self) -> App {
-	App::new("Rustybot")
-	    .subcommand(
-		App::new("status").arg(
-		    Arg::with_name("url")
-		)
-	    ).subcommand(
-		App::new("monitor").arg(
-		    Arg::with_name("url")
-		)
-	    ).subcommand(
-		App::new("vonitor").arg(
-		    Arg::with_name("url")
-		)
-	    ).subcommand(
-		App::new("konitor").arg(
-		    Arg::with_name("url")
-		)
-	    ).subcommand(
-		App::new("kronitor")
-		    .arg(
-			Arg::with_name("url")
-		    ).arg(
-			Arg::with_name("size")
-			    .long("size")
-			    .short("s")
-			    .takes_value(true)
-		    )
-	    ).subcommand(
-		App::new("help")
-	    ).subcommand(
-		App::new("ec2")
-		    .subcommand(
-			App::new("info").arg(
-			    Arg::with_name("url_or_id")
-			)
-		    ).subcommand(
-			App::new("start").arg(
-			    Arg::with_name("url_or_id")
-			)
-		    ).subcommand(
-			App::new("stop").arg(
-			    Arg::with_name("url_or_id")
-			)
-		    ).subcommand(
-			App::new("resize")
-			    .arg(
-				Arg::with_name("url_or_id")
-			    )
-			    .arg(
-				Arg::with_name("size")
-				    .long("size")
-				    .short("s")
-				    .takes_value(true)
-			    )
-		    ).subcommand(
-			App::new("ls").arg(
-			    Arg::with_name("filter")
-				.long("filter")
-				.short("f")
-				.takes_value(true)
-				.multiple(true)
-			).arg(
-			    Arg::with_name("limit")
-				.long("limit")
-				.short("l")
-				.takes_value(true)
-			)
-		    )
-	    )
-    }
-
-    fn poll_indexer(&self, parsed_url: String, message: &MessageStandard) -> Result<(), Box<dyn Error>> {
-	self.say(&message.channel, &format!("START monitoring {}", &parsed_url), true);
-	let mut count: usize = 0;
-	loop {
-	    let result = get_indexer_results(&parsed_url);
-	    if let Ok(result) = result {
-		if result.status == "indexing" {
-		    count = 0;
-		} else if result.status == "waiting" {
-		    if count >= 13 {
-			let value = format!("DONE monitoring {}: {:?}", &parsed_url, result);
-			self.say(&message.channel, &value, true);
-			return Ok(());
-		    }
-		    count += 1;
-		}
-	    } else {
-		self.say(&message.channel, &"Bad response, aborting", true);
-		return Err("Bad response".into());
-	    }
-	    if self.should_stop() {
+    pub fn wait_until(&self, fire_at: DateTime<Utc>) -> bool {
+	while Utc::now() < fire_at {
+	    if self.should_stop() || self.sibling_failed.load(Ordering::Relaxed) {
 		println!{"Cancelling"};
-		return Err("Cancelling".into());
+		return false;
 	    }
-	    thread::sleep(time::Duration::from_secs(5));
+	    thread::sleep(time::Duration::from_secs(1));
 	}
+	!self.should_stop() && !self.sibling_failed.load(Ordering::Relaxed)
     }
 
-    fn poll_visindexer(&self, parsed_url: String, message: &MessageStandard) -> Result<(), Box<dyn Error>> {
-	self.say(&message.channel, &format!("START monitoring vis_indexer {}", &parsed_url), true);
-	thread::sleep(time::Duration::from_secs(60));
-	let mut count: usize = 0;
-	loop {
-	    let result = get_visindexer_results(&parsed_url);
-	    if let Ok(result) = result {
-		if result.status == "indexing" {
-		    count = 0;
-		} else if result.status == "waiting" {
-		    if count >= 13 {
-			let value = format!("DONE monitoring vis_indexer {}: {:?}", &parsed_url, result);
-			self.say(&message.channel, &value, true);
-			return Ok(());
-		    }
-		    count += 1;
-		}
-	    } else {
-		self.say(&message.channel, &"Bad response, aborting", true);
-		return Err("Bad response".into());
-	    }
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return Err("Cancelling".into());
-	    }
-	    thread::sleep(time::Duration::from_secs(5));
+    // Posts new indexer problems to the job thread. Returns false when the
+    // monitor should abort.
+    fn report_problems(&self, parsed_url: &str, message: &MessageStandard, options: &MonitorOptions, tracker: &mut ProblemTracker, result: &Indexer) -> bool {
+	let problems = tracker.new_problems(result);
+	if problems.is_empty() {
+	    return true;
 	}
-    }
-
-    fn command_monitor(&self,  monitor: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url) = self.get_url_value_and_parse(monitor) {
-	    self.poll_indexer(parsed_url, message);
-	    return;
+	self.say_in_thread(message, &problems.join("\n"), true);
+	if options.on_error == OnError::Abort {
+	    self.say(&message.channel, &format!("Indexer problems on {}, aborting", parsed_url), true);
+	    return false;
 	}
-	self.say(&message.channel, &"Bad input", true);
+	true
     }
 
-    fn command_vonitor(&self,  vonitor: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url) = self.get_url_value_and_parse(vonitor) {
-	    self.poll_visindexer(parsed_url, message);
+    // Records the response and posts throughput and ETA to the job thread
+    // every few minutes while indexing.
+    fn report_progress(&self, parsed_url: &str, message: &MessageStandard, progress: &mut Progress, result: &Indexer) {
+	let now = Utc::now();
+	if result.status != "indexing" {
 	    return;
 	}
-	self.say(&message.channel, &"Bad input", true);
-    }
-
-    fn command_konitor(&self,  konitor: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url) = self.get_url_value_and_parse(konitor) {
-	    let polling = self.poll_indexer(parsed_url.to_owned(), message);
-	    if polling.is_err() {
-		return;
-	    }
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return;
-	    }
-	    let vispolling = self.poll_visindexer(parsed_url.to_owned(), message);
-	    if vispolling.is_err() {
-		return;
-	    }
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return;
-	    }
-	    self.command_ec2_stop(konitor, message);
-	    return;
+	if let Some(sample) = result.sample(now) {
+	    progress.record(sample);
+	}
+	if let Some(estimate) = progress.report_due(now) {
+	    self.say_in_thread(message, &format!("PROGRESS {}: {}", parsed_url, estimate.describe()), true);
 	}
-	self.say(&message.channel, &"Bad input", true);
     }
 
-    fn command_kronitor(&self,  kronitor: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url) = self.get_url_value_and_parse(kronitor) {
-	    let polling = self.poll_indexer(parsed_url.to_owned(), message);
-	    if polling.is_err() {
-		return;
-	    }
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return;
-	    }
-	    let vispolling = self.poll_visindexer(parsed_url.to_owned(), message);
-	    if vispolling.is_err() {
-		return;
-	    }
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return;
-	    }
-	    self.command_ec2_stop(kronitor, message);
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return;
+    // Polls `path` on `parsed_url` until `options.until` holds for
+    // `options.settle` consecutive responses and returns the last one.
+    // `on_response` sees every response and stops polling by returning false.
+    fn poll_json<T: DeserializeOwned>(&self, parsed_url: &str, path: &str, default_delay: Duration, message: &MessageStandard, options: &MonitorOptions, mut on_response: impl FnMut(&T) -> bool) -> Result<T, Box<dyn Error>> {
+	let initial_delay = options.initial_delay.unwrap_or(default_delay);
+	if initial_delay > Duration::zero() && !self.wait_until(Utc::now() + initial_delay) {
+	    return Err("Cancelling".into());
+	}
+	let mut count: usize = 0;
+	let mut failures: usize = 0;
+	loop {
+	    let response: Result<(T, Value), Box<dyn Error>> = http::get_json(parsed_url, path, &options.http)
+		.map_err(|error| error.into())
+		.and_then(|json| Ok((serde_json::from_value(json.clone())?, json)));
+	    match response {
+		Ok((result, json)) => {
+		    failures = 0;
+		    if !on_response(&result) {
+			return Err("Stopped".into());
+		    }
+		    if options.until.evaluate(&json) {
+			count += 1;
+			if count >= options.settle {
+			    return Ok(result);
+			}
+		    } else {
+			count = 0;
+		    }
+		},
+		Err(error) => {
+		    failures += 1;
+		    if failures >= options.max_failures {
+			self.say(&message.channel, &format!("Bad response from {}, aborting: {}", parsed_url, error), true);
+			return Err("Bad response".into());
+		    }
+		    let value = format!(
+			"Bad response from {} ({} of {} failures allowed): {}",
+			parsed_url,
+			failures,
+			options.max_failures,
+			error
+		    );
+		    self.say_in_thread(message, &value, true);
+		},
 	    }
-	    self.say(&message.channel, &"Waiting to resize", true);
-	    thread::sleep(time::Duration::from_secs(120));
-	    if self.should_stop() {
-		println!{"Cancelling"};
-		return;
+	    if !self.wait_until(Utc::now() + options.interval) {
+		return Err("Cancelling".into());
 	    }
-	    self.command_ec2_resize(kronitor, message);
-	    return;
 	}
-	self.say(&message.channel, &"Bad input", true);
     }
 
-    fn command_status(&self, status: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url) = self.get_url_value_and_parse(status) {
-	    let result = get_indexer_results(&parsed_url);
-	    if let Ok(result) = result {
-		let value = format!("{:?}", result);
-		self.say(&message.channel, &value, true);
-		thread::sleep(time::Duration::from_secs(3));
-		return;
+    pub fn poll_indexer(&self, indexer: &IndexerEndpoint, parsed_url: &str, message: &MessageStandard, options: &MonitorOptions) -> Result<(), Box<dyn Error>> {
+	self.say(&message.channel, &format!("START monitoring {}{}", indexer.label, parsed_url), true);
+	let mut tracker = ProblemTracker::default();
+	let mut progress = Progress::default();
+	let default_delay = Duration::seconds(indexer.initial_delay);
+	let result = self.poll_json(parsed_url, indexer.path, default_delay, message, options, |result: &Indexer| {
+	    if !self.report_problems(parsed_url, message, options, &mut tracker, result) {
+		return false;
 	    }
-	}
-	self.say(&message.channel, &"Bad input", true);
+	    self.report_progress(parsed_url, message, &mut progress, result);
+	    true
+	})?;
+	self.record_cycles(parsed_url, indexer, &result);
+	let value = format!("DONE monitoring {}{}\n{}", indexer.label, parsed_url, result.summary());
+	self.say(&message.channel, &value, true);
+	Ok(())
     }
 
-    fn command_help(&self,  help: &ArgMatches, message: &MessageStandard) {
-	self.say(&message.channel, constants::HELP, false);
-    }
-
-    fn command_ec2_info(&self, info: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url_or_id) = self.get_url_or_id_value_and_parse(info) {
-	    let ec2 = make_ec2_client();
-	    let instance_info = get_instance_info_from_url_or_id(
-		&ec2,
-		parsed_url_or_id.clone()
-	    );
-	    if !instance_info.is_empty() {
-		self.say(&message.channel, &format!("Getting instance info for {}", &parsed_url_or_id), true);
-		let value = format!("{:?}", instance_info);
-		self.say(&message.channel, &value, true);
-		thread::sleep(time::Duration::from_secs(3));
-		return;
-	    }
+    // Monitors every URL on its own thread. Each one reports when it finishes
+    // and a summary follows once all of them have stopped.
+    pub fn poll_indexer_urls(&self, indexer: &IndexerEndpoint, parsed_urls: &[String], message: &MessageStandard, options: &MonitorOptions) -> Result<(), Box<dyn Error>> {
+	if let [parsed_url] = parsed_urls {
+	    return self.poll_indexer(indexer, parsed_url, message, options);
 	}
-	self.say(&message.channel, &"Bad input", true);
-    }
-
-    fn command_ec2_start(&self, start: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url_or_id) = self.get_url_or_id_value_and_parse(start) {
-	    let ec2 = make_ec2_client();
-	    let started_instance = start_instance_by_url_or_id(
-		&ec2,
-		parsed_url_or_id.clone()
-	    );
-	    if let Ok(started_instance) = started_instance {
-		self.say(&message.channel, &format!("Starting instance {}", &parsed_url_or_id), true);
-		let value = format!("{:?}", started_instance);
-		self.say(&message.channel, &value, true);
-		thread::sleep(time::Duration::from_secs(3));
-		return;
-	    }
+	let finished: Vec<bool> = thread::scope(|scope| {
+	    let handles: Vec<_> = parsed_urls.iter()
+		.map(|parsed_url| scope.spawn(move || self.poll_indexer(indexer, parsed_url, message, options).is_ok()))
+		.collect();
+	    handles.into_iter().map(|handle| handle.join().unwrap_or(false)).collect()
+	});
+	let outcome = if self.should_stop() { "cancelled" } else { "failed" };
+	let mut lines = vec![format!(
+	    "FINISHED monitoring {}{} URLs, {} of {} done",
+	    indexer.label,
+	    parsed_urls.len(),
+	    finished.iter().filter(|done| **done).count(),
+	    parsed_urls.len()
+	)];
+	for (parsed_url, done) in parsed_urls.iter().zip(finished.iter()) {
+	    lines.push(format!("    {}: {}", parsed_url, if *done { "done" } else { outcome }));
 	}
-	self.say(&message.channel, &"Bad input", true);
-    }
-
-    fn command_ec2_stop(&self, stop: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url_or_id) = self.get_url_or_id_value_and_parse(stop) {
-	    let ec2 = make_ec2_client();
-	    let stopped_instance = stop_instance_by_url_or_id(
-		&ec2,
-		parsed_url_or_id.clone()
-	    );
-	    if let Ok(stopped_instance) = stopped_instance {
-		self.say(&message.channel, &format!("Stopping instance {}", &parsed_url_or_id), true);
-		let value = format!("{:?}", stopped_instance);
-		self.say(&message.channel, &value, true);
-		thread::sleep(time::Duration::from_secs(3));
-		return;
-	    }
+	self.say(&message.channel, &lines.join("\n"), true);
+	if finished.iter().all(|done| *done) {
+	    return Ok(());
 	}
-	self.say(&message.channel, &"Bad input", true);
-    }
-
-    fn command_ec2_resize(&self, resize: &ArgMatches, message: &MessageStandard) {
-	if let Some(parsed_url_or_id) = self.get_url_or_id_value_and_parse(resize) {
-	    let ec2 = make_ec2_client();
-	    let size = resize.value_of("size").unwrap_or(
-		constants::RESIZE_INSTANCE
-	    );
-	    let resized_instance = resize_instance_by_url_or_id(
-		&ec2,
-		parsed_url_or_id.clone(),
-		size.to_owned(),
-	    );
-	    match resized_instance {
-		Ok(_) => {
-		    let value = format!(
-			"Resized instance {} to {}: {:?}",
-			&parsed_url_or_id,
-			&size,
-			get_instance_info_from_url_or_id(&ec2, parsed_url_or_id.clone())
-		    );
-		    self.say(&message.channel, &value, true);
-		    thread::sleep(time::Duration::from_secs(3));
-		    return;
-		}
-		Err(error) => {
-		    let value = format!("{}", error);
-		    self.say(&message.channel, &value, true);
-		    thread::sleep(time::Duration::from_secs(3));
-		    return;
-		}
-	    }
-	}
-	self.say(&message.channel, &"Bad input", true);
+	Err("Not all URLs finished".into())
     }
 
-    fn command_ec2_ls(&self, list: &ArgMatches, message: &MessageStandard) {
-        let ec2 = make_ec2_client();
-	let filters = list.values_of("filter")
-	    .unwrap_or(Values::default())
-	    .map(
-		|x| {
-		    let values = x.split("=").collect::<Vec<_>>();
-		    if values.len() == 2 {
-			return Some((values[0].to_string(), values[1].to_string()));
-		    } else {
-			return None;
-		    }
+    // Waits on every indexer in `options.indexers`, one after the other or
+    // all at once with `options.parallel`.
+    pub fn poll_indexers(&self, parsed_url: &str, message: &MessageStandard, options: &MonitorOptions) -> Result<(), Box<dyn Error>> {
+	if !options.parallel {
+	    for indexer in options.indexers.iter() {
+		self.poll_indexer(indexer, parsed_url, message, options)?;
+		if self.should_stop() {
+		    return Err("Cancelling".into());
 		}
-	    )
-	    .filter(|x| x.is_some())
-	    .map(|x| x.unwrap())
-	    .collect::<Vec<_>>();
-	let limit = list.value_of("limit").unwrap_or("3").parse::<usize>().unwrap_or(3);
-        let matching_instances = get_instance_info_from_filters(&ec2, filters);
-	if let Ok(matches) = matching_instances {
-	    let results =  matches.iter().enumerate().collect::<Vec<_>>();
-	    let total = results.len();
-	    let value = format!(
-		"Showing {} out of {}:\n{:?}",
-		if limit <= total {limit} else {total},
-		results.len(),
-		results.iter().take(limit).collect::<Vec<_>>()
-	    );
-	    self.say(&message.channel, &value, true);
-	    thread::sleep(time::Duration::from_secs(3));
-	    return;
+	    }
+	    return Ok(());
 	}
-	self.say(&message.channel, &"Bad input", true);
-    }
-
-    fn handle_matches(&self, matches: ArgMatches, message: &MessageStandard) {
-	match matches.subcommand() {
-	    ("status", Some(status)) => self.command_status(status, &message),
-	    ("monitor", Some(monitor)) => self.command_monitor(monitor, &message),
-	    ("vonitor", Some(vonitor)) => self.command_vonitor(vonitor, &message),
-	    ("konitor", Some(konitor)) => self.command_konitor(konitor, &message),
-	    ("kronitor", Some(kronitor)) => self.command_kronitor(kronitor, &message),
-	    ("help", Some(help)) => self.command_help(help, &message),
-	    ("ec2", Some(ec2)) => {
-		match ec2.subcommand() {
-		    ("info", Some(info)) => self.command_ec2_info(info, &message),
-		    ("start", Some(start)) => self.command_ec2_start(start, &message),
-		    ("stop", Some(stop)) => self.command_ec2_stop(stop, &message),
-		    ("resize", Some(resize)) => self.command_ec2_resize(resize, &message),
-		    ("ls", Some(ls)) => self.command_ec2_ls(ls, &message),
- 		    _ => ()
-		}
-	    },
-	    _ => ()
+	let finished = thread::scope(|scope| {
+	    let handles: Vec<_> = options.indexers.iter()
+		.map(|indexer| scope.spawn(move || {
+		    if self.poll_indexer(indexer, parsed_url, message, options).is_ok() {
+			return true;
+		    }
+		    if self.sibling_failed.swap(true, Ordering::Relaxed) && !self.should_stop() {
+			self.say(&message.channel, &format!("STOPPED monitoring {}{}, another indexer failed", indexer.label, parsed_url), true);
+		    }
+		    false
+		}))
+		.collect();
+	    handles.into_iter().all(|handle| handle.join().unwrap_or(false))
+	});
+	self.sibling_failed.store(false, Ordering::Relaxed);
+	if !finished || self.should_stop() {
+	    return Err("Indexers did not finish".into());
 	}
+	Ok(())
+    }
+
+    // Polls any JSON endpoint until `options.until` holds.
+    pub fn watch(&self, parsed_url: String, path: &str, message: &MessageStandard, options: &MonitorOptions) -> Result<(), Box<dyn Error>> {
+	let endpoint = http::endpoint(&parsed_url, path);
+	self.say(&message.channel, &format!("START watching {}", &endpoint), true);
+	let result: Value = self.poll_json(&parsed_url, path, Duration::zero(), message, options, |_| true)?;
+	let json = serde_json::to_string_pretty(&result)?;
+	self.say(&message.channel, &format!("DONE watching {}\n```{}```", &endpoint, json), true);
+	Ok(())
     }
 
     pub fn handle_message(&self, message: MessageStandard) {
 	let text = self.unwrap_string(&message.text);
-	let app = self.make_app();
-	let matches = app.get_matches_from_safe(
-	    text.split(' ').collect::<Vec<_>>()
-	);
+	let tokens = match tokenize(text) {
+	    Ok(tokens) => tokens,
+	    Err(error) => {
+		self.say(&message.channel, &format!("Could not parse message: {}", error), false);
+		return;
+	    }
+	};
+	let (bin, args) = tokens.split_first().map_or((String::new(), vec![]), |(bin, args)| (bin.to_owned(), args.to_vec()));
+	let args = self.registry.expand_alias(args);
+	let app = self.registry.make_app();
+	let matches = app.get_matches_from_safe(std::iter::once(bin).chain(args.iter().cloned()));
 	match matches {
-	    Ok(matches) => self.handle_matches(matches, &message),
+	    Ok(matches) => self.registry.dispatch(self, &matches, &message),
 	    Err(error) => {
-		self.say(&message.channel, self.get_random_emoji(), false);
+		let value = match self.registry.suggest(&args) {
+		    Some(suggestion) => suggestion,
+		    None => format!("```{}```", error.message.trim_end()),
+		};
+		self.say(&message.channel, &value, false);
 	    }
 	}
     }
 }
 
 
-impl<'a> Drop for RustyBot<'a> {
+impl Drop for RustyBot {
     fn drop(&mut self) {
         println!("Dropping!");
 	self.tx.send(self.worker_id.clone()).unwrap();
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    const INDEXER_BODY: &str = r#"{
+        "title": "Primary Indexer",
+        "status": "indexing",
+        "docs_in_index": 586334,
+        "uuids_in_progress": 1200,
+        "uuids_troubled": 0,
+        "now": "2020-06-01T12:10:00.000000",
+        "listener": {"status": "indexing", "started": "2020-06-01T11:00:00.000000", "timestamp": "2020-06-01T12:10:00"},
+        "state": {
+            "status": "indexing",
+            "xmin": 1000,
+            "invalidated": 1234,
+            "cycle_started": "2020-06-01T12:05:00.000000",
+            "errors": [{"uuid": "abc", "error_message": "Timeout"}]
+        },
+        "results": [
+            {
+                "status": "done",
+                "xmin": 900,
+                "last_xmin": 800,
+                "invalidated": 500,
+                "indexed": 500,
+                "cycle_started": "2020-06-01T11:00:00.000000",
+                "cycle_took": "0:01:30.500000",
+                "errors": null,
+                "snapshot_id": "00000003-1"
+            }
+        ],
+        "registered_indexers": {"vis_indexer": "waiting"}
+    }"#;
+
+
+    #[test]
+    fn test_deserialize_indexer() {
+	let indexer: Indexer = serde_json::from_str(INDEXER_BODY).unwrap();
+	assert_eq!(indexer.status, "indexing");
+	assert_eq!(indexer.uuids_being_indexed(), Some(1200));
+	assert_eq!(indexer.listener.as_ref().unwrap().status, Some("indexing".to_owned()));
+	assert_eq!(indexer.results[0].extra.get("snapshot_id"), Some(&Value::from("00000003-1")));
+	assert!(indexer.extra.contains_key("registered_indexers"));
+	assert_eq!(indexer.errors().len(), 1);
+	let minimal: Indexer = serde_json::from_str(r#"{"status": "waiting"}"#).unwrap();
+	assert_eq!(minimal.results, vec![]);
+	assert_eq!(minimal.summary(), "Status: waiting");
+    }
+
+
+    #[test]
+    fn test_indexer_cycle_estimate() {
+	let mut indexer: Indexer = serde_json::from_str(INDEXER_BODY).unwrap();
+	assert_eq!(indexer.cycle_estimate(), None);
+	indexer.state.as_mut().unwrap().indexed = Some(3000);
+	let estimate = indexer.cycle_estimate().unwrap();
+	assert_eq!(estimate.describe(), "1200 queued, 10.0 UUIDs/s, ETA 2m 00s");
+	assert!(indexer.summary().contains("\nProgress: 1200 queued, 10.0 UUIDs/s, ETA 2m 00s\n"));
+	indexer.state.as_mut().unwrap().indexing_elapsed = Some("0:10:00".to_owned());
+	assert_eq!(indexer.cycle_estimate().unwrap().per_second, 5.0);
+	assert_eq!(indexer.sample(Utc::now()).unwrap().indexed, Some(3000));
+    }
+
+
+    #[test]
+    fn test_indexer_fields() {
+	let indexer: Indexer = serde_json::from_str(INDEXER_BODY).unwrap();
+	let fields = indexer.fields();
+	assert_eq!(fields[0], ("Status", "indexing".to_owned()));
+	assert_eq!(fields[2], ("UUIDs being indexed", "1200".to_owned()));
+	assert_eq!(fields[4], ("Last cycle took", "0:01:30.500000".to_owned()));
+	assert_eq!(fields[7], ("Errors", "1".to_owned()));
+	let minimal: Indexer = serde_json::from_str(r#"{"status": "waiting"}"#).unwrap();
+	assert_eq!(minimal.fields()[1], ("Docs in index", "-".to_owned()));
+    }
+
+
+    // Returns the receiver too, since dropping the bot reports to it.
+    fn make_bot() -> (crossbeam_channel::Receiver<String>, RustyBot) {
+	let (tx, rx) = crossbeam_channel::unbounded();
+	let bot = RustyBot::new(
+	    "token".to_owned(),
+	    "1000".to_owned(),
+	    tx,
+	    Arc::new(AtomicBool::new(false)),
+	    Arc::new(Registry::new()),
+	    Arc::new(History::new(std::env::temp_dir().join("rustybot_bot_test_history.json"))),
+	    Arc::new(Audit::new(std::env::temp_dir().join("rustybot_bot_test_audit.log")))
+	);
+	(rx, bot)
+    }
+
+
+    #[test]
+    fn test_wait_until_stops_when_a_sibling_fails() {
+	let (_rx, bot) = make_bot();
+	assert!(bot.wait_until(Utc::now()));
+	bot.sibling_failed.store(true, Ordering::Relaxed);
+	let started = Utc::now();
+	assert!(!bot.wait_until(started + Duration::hours(1)));
+	assert!(Utc::now() - started < Duration::seconds(5));
+	assert!(!bot.should_stop());
+    }
+
+
+    #[test]
+    fn test_parse_mention_id() {
+	assert_eq!(parse_mention_id("@U0123ABC"), Some("U0123ABC"));
+	assert_eq!(parse_mention_id("@W0123ABC"), Some("W0123ABC"));
+	assert_eq!(parse_mention_id("@keenan"), None);
+	assert_eq!(parse_mention_id("@UABC"), None);
+	assert_eq!(parse_mention_id("U0123ABC"), None);
+    }
+
+
+    #[test]
+    fn test_parse_cycle_took() {
+	assert_eq!(parse_cycle_took("0:01:30.5"), Some(Duration::milliseconds(90500)));
+	assert_eq!(parse_cycle_took("0:00:05.123456"), Some(Duration::microseconds(5123456)));
+	assert_eq!(parse_cycle_took("2 days, 1:00:00"), Some(Duration::hours(49)));
+	assert_eq!(parse_cycle_took("1:00"), None);
+	assert_eq!(parse_cycle_took("99999999999999 days, 0:00:00"), None);
+	assert_eq!(parse_cycle_took("9999999999999999:00:00"), None);
+	assert_eq!(parse_cycle_took("99999999999999999999 days, 0:00:00"), None);
+    }
+
+
+    #[test]
+    fn test_cycle_finished() {
+	let result = |took: &str| serde_json::from_value::<IndexerResult>(serde_json::json!({
+	    "cycle_started": "2020-06-01T11:00:00",
+	    "cycle_took": took
+	})).unwrap();
+	assert_eq!(
+	    result("0:01:30").cycle_finished(),
+	    NaiveDateTime::parse_from_str("2020-06-01T11:01:30", "%Y-%m-%dT%H:%M:%S").ok()
+	);
+	assert_eq!(result("100000000 days, 0:00:00").cycle_finished(), None);
+    }
+
+
+    #[test]
+    fn test_indexer_summary() {
+	let indexer: Indexer = serde_json::from_str(INDEXER_BODY).unwrap();
+	assert_eq!(
+	    indexer.summary(),
+	    [
+		"Status: indexing",
+		"UUIDs being indexed: 1200",
+		"Docs in index: 586334",
+		"Current cycle: started 2020-06-01T12:05:00.000000, 1234 invalidated, errors: 1",
+		"Last cycle: started 2020-06-01T11:00:00.000000, finished 2020-06-01T11:01:30, took 0:01:30.500000, indexed 500 of 500 invalidated",
+		"Listener: indexing",
+		"Errors (1):",
+		"    abc: Timeout",
+	    ].join("\n")
+	);
+    }
+}
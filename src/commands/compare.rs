@@ -0,0 +1,144 @@
+use crate::bot::{self, RustyBot};
+use crate::http::HttpOptions;
+use crate::monitor::INDEXERS;
+use crate::registry::Command;
+use clap::{Arg, ArgMatches};
+use slack_api::MessageStandard;
+use std::{thread, time};
+
+
+type Fields = Result<Vec<(&'static str, String)>, String>;
+
+
+fn host(url: &str) -> &str {
+    url.trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/')
+}
+
+
+// Lines up `left` and `right` in columns and marks rows that differ with `*`.
+fn format_side_by_side(headers: (&str, &str), left: &Fields, right: &Fields) -> String {
+    let mut rows = vec![];
+    match (left, right) {
+	(Ok(left), Ok(right)) => {
+	    for ((name, left), (_, right)) in left.iter().zip(right.iter()) {
+		rows.push((name.to_string(), left.clone(), right.clone()));
+	    }
+	},
+	(Err(left), Err(right)) if left == right => rows.push(("Error".to_owned(), left.clone(), right.clone())),
+	_ => {
+	    let describe = |fields: &Fields| match fields {
+		Ok(fields) => fields.first().map(|(_, status)| status.clone()).unwrap_or_default(),
+		Err(error) => error.clone(),
+	    };
+	    rows.push(("Status".to_owned(), describe(left), describe(right)));
+	},
+    }
+    let name_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+    let left_width = rows.iter().map(|row| row.1.len()).chain(Some(headers.0.len())).max().unwrap_or(0);
+    let mut lines = vec![format!("  {:name_width$}  {:left_width$}  {}", "", headers.0, headers.1)];
+    for (name, left, right) in rows.iter() {
+	let marker = if left != right { "*" } else { " " };
+	lines.push(format!("{} {:name_width$}  {:left_width$}  {}", marker, name, left, right));
+    }
+    lines.iter().map(|line| line.trim_end()).collect::<Vec<_>>().join("\n")
+}
+
+
+pub struct Compare;
+
+
+impl Command for Compare {
+    fn name(&self) -> &'static str {
+	"compare"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL_A] [URL_B]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Compare indexer state of two deployments side by side"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["compare https://v102rc2.demo.encodedcc.org/ https://www.encodeproject.org/"]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    Arg::with_name("url")
+		.multiple(true)
+		.help("The two demo or production URLs to compare")
+	]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed_urls = match bot.get_url_values_and_parse(matches) {
+	    Ok(parsed_urls) if parsed_urls.len() == 2 => parsed_urls,
+	    Ok(parsed_urls) => {
+		let error = format!("Expected two URLs, got {}", parsed_urls.len());
+		return bot.say_usage_error(&message.channel, &[self.name()], &error);
+	    },
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	let options = HttpOptions::from_env();
+	let fetch = |url: &str, path: &str| -> Fields {
+	    bot::get_indexer(url, path, &options)
+		.map(|indexer| indexer.fields())
+		.map_err(|error| error.to_string())
+	};
+	let mut sections = vec![format!("Comparing {} with {}", &parsed_urls[0], &parsed_urls[1])];
+	for indexer in INDEXERS.iter() {
+	    let left = fetch(&parsed_urls[0], indexer.path);
+	    let right = fetch(&parsed_urls[1], indexer.path);
+	    let table = format_side_by_side((host(&parsed_urls[0]), host(&parsed_urls[1])), &left, &right);
+	    sections.push(format!("*{}*\n```{}```", indexer.path, table));
+	}
+	bot.say(&message.channel, &sections.join("\n"), true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_format_side_by_side() {
+	let left: Fields = Ok(vec![("Status", "waiting".to_owned()), ("Docs in index", "586334".to_owned())]);
+	let right: Fields = Ok(vec![("Status", "indexing".to_owned()), ("Docs in index", "586334".to_owned())]);
+	assert_eq!(
+	    format_side_by_side(("a.org", "www.b.org"), &left, &right),
+	    [
+		"                 a.org    www.b.org",
+		"* Status         waiting  indexing",
+		"  Docs in index  586334   586334",
+	    ].join("\n")
+	);
+    }
+
+
+    #[test]
+    fn test_format_side_by_side_with_errors() {
+	let left: Fields = Ok(vec![("Status", "waiting".to_owned())]);
+	let right: Fields = Err("404 Not Found".to_owned());
+	assert_eq!(
+	    format_side_by_side(("a.org", "b.org"), &left, &right),
+	    "          a.org    b.org\n* Status  waiting  404 Not Found"
+	);
+	let left: Fields = Err("404 Not Found".to_owned());
+	assert_eq!(
+	    format_side_by_side(("a.org", "b.org"), &left, &right),
+	    "         a.org          b.org\n  Error  404 Not Found  404 Not Found"
+	);
+    }
+
+
+    #[test]
+    fn test_host() {
+	assert_eq!(host("https://www.encodeproject.org/"), "www.encodeproject.org");
+	assert_eq!(host("http://test.encodedcc.org"), "test.encodedcc.org");
+    }
+}
@@ -0,0 +1,775 @@
+use crate::aws::{self, AwsConfig, AwsError, InstanceInfo, Resolved, Target};
+use crate::aws::make_ec2_client;
+use crate::aws::resolve_instance;
+use crate::aws::get_instance_info_from_filters;
+use crate::bot::RustyBot;
+use crate::commands::waitup;
+use crate::constants;
+use crate::registry::Command;
+use chrono::Duration;
+use clap::{Arg, ArgMatches};
+use rusoto_ec2::Ec2Client;
+use slack_api::MessageStandard;
+use std::error::Error;
+use std::{thread, time};
+
+
+pub fn url_or_id_arg() -> Arg<'static, 'static> {
+    Arg::with_name("url_or_id")
+	.help("Demo URL, instance ID, demo name, Jira ticket, commit hash prefix or @user")
+}
+
+
+pub fn target_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+	Arg::with_name("region")
+	    .long("region")
+	    .takes_value(true)
+	    .help("AWS region (default first of RUSTY_BOT_AWS_REGIONS)"),
+	Arg::with_name("profile")
+	    .long("profile")
+	    .takes_value(true)
+	    .help("AWS credentials profile to use"),
+	Arg::with_name("account")
+	    .long("account")
+	    .takes_value(true)
+	    .help("Account from RUSTY_BOT_AWS_ACCOUNTS whose role is assumed"),
+    ]
+}
+
+
+// Returns one target per region in --region or, when it is missing, the
+// default region or every configured region if `all_regions` is set.
+pub fn parse_targets(matches: &ArgMatches, all_regions: bool) -> Result<Vec<Target>, Box<dyn Error>> {
+    let config = AwsConfig::from_env();
+    let regions = match matches.value_of("region") {
+	Some(region) => aws::parse_regions(region)?,
+	None if all_regions => config.regions.clone(),
+	None => vec![config.default_region()],
+    };
+    let account = matches.value_of("account")
+	.map(|name| config.account(name))
+	.transpose()?;
+    let profile = matches.value_of("profile").map(str::to_owned);
+    Ok(regions.into_iter().map(|region| Target {
+	region,
+	profile: profile.clone(),
+	account: account.clone()
+    }).collect())
+}
+
+
+pub fn parse_target(matches: &ArgMatches) -> Result<Target, Box<dyn Error>> {
+    let mut targets = parse_targets(matches, false)?;
+    if targets.len() > 1 {
+	return Err("Expected a single region".into());
+    }
+    Ok(targets.remove(0))
+}
+
+
+// Makes a client for `target`, telling the channel when that fails.
+pub fn connect(bot: &RustyBot, message: &MessageStandard, target: &Target) -> Option<Ec2Client> {
+    match make_ec2_client(target) {
+	Ok(ec2) => Some(ec2),
+	Err(error) => {
+	    bot.say(&message.channel, &format!("Could not connect to {}: {}", target.label(), error), true);
+	    None
+	},
+    }
+}
+
+
+// Resolves `url_or_id` to one instance, telling the channel why it could
+// not when that fails.
+fn resolve_or_say(bot: &RustyBot, message: &MessageStandard, ec2: &Ec2Client, url_or_id: &str, target: &Target, action: &str) -> Option<Resolved> {
+    match resolve_instance(ec2, url_or_id) {
+	Ok(resolved) => Some(resolved),
+	Err(error) => {
+	    bot.say(&message.channel, &format!("Could not {} {} in {}: {}", action, url_or_id, target.label(), error), true);
+	    None
+	},
+    }
+}
+
+
+// Stops the instance unless it is already stopped and waits until it is,
+// reporting each state in the thread of `message`.
+fn stop_and_wait(bot: &RustyBot, message: &MessageStandard, ec2: &Ec2Client, resolved: &Resolved, max_wait: Duration) -> Result<(), AwsError> {
+    if resolved.instance.state() == "stopped" {
+	return Ok(());
+    }
+    aws::stop_instance(ec2, resolved)?;
+    bot.say_in_thread(message, &format!("Stopping instance {}", resolved.id()), true);
+    waitup::wait_for_state(bot, ec2, resolved.id(), "stopped", message, max_wait)?;
+    Ok(())
+}
+
+
+fn parse_url_or_id_and_target(bot: &RustyBot, matches: &ArgMatches) -> Result<(String, Target), Box<dyn Error>> {
+    let parsed_url_or_id = bot.get_url_or_id_value_and_parse(matches)?;
+    Ok((parsed_url_or_id, parse_target(matches)?))
+}
+
+
+pub struct Ec2 {
+    commands: Vec<Box<dyn Command>>
+}
+
+
+impl Ec2 {
+    pub fn new() -> Self {
+	Ec2 {
+	    commands: vec![
+		Box::new(Info),
+		Box::new(Start),
+		Box::new(Stop),
+		Box::new(Reboot),
+		Box::new(Restart),
+		Box::new(Resize),
+		Box::new(Terminate),
+		Box::new(Ls),
+	    ]
+	}
+    }
+}
+
+
+impl Command for Ec2 {
+    fn name(&self) -> &'static str {
+	"ec2"
+    }
+
+    fn about(&self) -> &'static str {
+	"Manage EC2 instances"
+    }
+
+    fn subcommands(&self) -> &[Box<dyn Command>] {
+	&self.commands
+    }
+
+    fn run(&self, _bot: &RustyBot, _matches: &ArgMatches, _message: &MessageStandard) {}
+}
+
+
+pub struct Info;
+
+
+impl Command for Info {
+    fn name(&self) -> &'static str {
+	"info"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Get instance info"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 info https://dev-84b292185-keenan.demo.encodedcc.org/",
+	    "ec2 info i-02e86c27e5d31f8d1",
+	    "ec2 info ENCD-5328",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![url_or_id_arg()], target_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let (parsed_url_or_id, target) = match parse_url_or_id_and_target(bot, matches) {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "get info for") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	bot.say(&message.channel, &format!("Getting instance info for {} in {}", resolved.describe(), target.label()), true);
+	let value = format!("{:?}", resolved.instance);
+	bot.say(&message.channel, &value, true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Start;
+
+
+impl Command for Start {
+    fn name(&self) -> &'static str {
+	"start"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE] --wait --max-wait [DURATION]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Start instance"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 start i-02e86c27e5d31f8d1",
+	    "ec2 start https://dev-84b292185-keenan.demo.encodedcc.org/ --wait",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    url_or_id_arg(),
+	    Arg::with_name("wait")
+		.long("wait")
+		.help("Wait until the instance is running and the URL answers"),
+	    waitup::max_wait_arg(),
+	].into_iter().chain(target_args()).collect()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed = parse_url_or_id_and_target(bot, matches)
+	    .and_then(|(parsed_url_or_id, target)| Ok((parsed_url_or_id, target, waitup::parse_max_wait(matches)?)));
+	let (parsed_url_or_id, target, max_wait) = match parsed {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "start") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	match aws::start_instance(&ec2, &resolved) {
+	    Ok(started_instance) => {
+		bot.say(&message.channel, &format!("Starting instance {} in {}", resolved.describe(), target.label()), true);
+		let value = format!("{:?}", started_instance);
+		bot.say(&message.channel, &value, true);
+		if matches.is_present("wait") {
+		    let result = waitup::wait_for_boot(bot, &ec2, &parsed_url_or_id, message, max_wait);
+		    return waitup::say_boot_result(bot, &parsed_url_or_id, message, result);
+		}
+	    },
+	    Err(error) => {
+		let value = format!("Could not start {} in {}: {}", &parsed_url_or_id, target.label(), error);
+		bot.say(&message.channel, &value, true);
+	    }
+	}
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Stop;
+
+
+impl Command for Stop {
+    fn name(&self) -> &'static str {
+	"stop"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Stop instance"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 stop https://dev-84b292185-keenan.demo.encodedcc.org/",
+	    "ec2 stop @keenan",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![url_or_id_arg()], target_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let (parsed_url_or_id, target) = match parse_url_or_id_and_target(bot, matches) {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "stop") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	match aws::stop_instance(&ec2, &resolved) {
+	    Ok(stopped_instance) => {
+		bot.say(&message.channel, &format!("Stopping instance {} in {}", resolved.describe(), target.label()), true);
+		let value = format!("{:?}", stopped_instance);
+		bot.say(&message.channel, &value, true);
+	    },
+	    Err(error) => {
+		let value = format!("Could not stop {} in {}: {}", &parsed_url_or_id, target.label(), error);
+		bot.say(&message.channel, &value, true);
+	    }
+	}
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Reboot;
+
+
+impl Command for Reboot {
+    fn name(&self) -> &'static str {
+	"reboot"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Reboot instance in place"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 reboot https://dev-84b292185-keenan.demo.encodedcc.org/",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![url_or_id_arg()], target_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let (parsed_url_or_id, target) = match parse_url_or_id_and_target(bot, matches) {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "reboot") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	let value = match aws::reboot_instance(&ec2, &resolved) {
+	    Ok(()) => format!("Rebooting instance {} in {}", resolved.describe(), target.label()),
+	    Err(error) => format!("Could not reboot {} in {}: {}", &parsed_url_or_id, target.label(), error),
+	};
+	bot.say(&message.channel, &value, true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Restart;
+
+
+impl Restart {
+    // Stops the instance unless it is already stopped, waits for the stopped
+    // state and starts it again.
+    fn stop_and_start(bot: &RustyBot, message: &MessageStandard, ec2: &Ec2Client, resolved: &Resolved, max_wait: Duration) -> Result<(), AwsError> {
+	stop_and_wait(bot, message, ec2, resolved, max_wait)?;
+	aws::start_instance(ec2, resolved)?;
+	bot.say_in_thread(message, &format!("Starting instance {}", resolved.id()), true);
+	Ok(())
+    }
+}
+
+
+impl Command for Restart {
+    fn name(&self) -> &'static str {
+	"restart"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE] --wait --max-wait [DURATION]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Stop instance, wait until it is stopped and start it again"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 restart i-02e86c27e5d31f8d1",
+	    "ec2 restart https://dev-84b292185-keenan.demo.encodedcc.org/ --wait",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    url_or_id_arg(),
+	    Arg::with_name("wait")
+		.long("wait")
+		.help("Wait until the instance is running again and the URL answers"),
+	    waitup::max_wait_arg(),
+	].into_iter().chain(target_args()).collect()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed = parse_url_or_id_and_target(bot, matches)
+	    .and_then(|(parsed_url_or_id, target)| Ok((parsed_url_or_id, target, waitup::parse_max_wait(matches)?)));
+	let (parsed_url_or_id, target, max_wait) = match parsed {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "restart") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	bot.say(&message.channel, &format!("Restarting instance {} in {}", resolved.describe(), target.label()), true);
+	match Restart::stop_and_start(bot, message, &ec2, &resolved, max_wait) {
+	    Ok(()) if matches.is_present("wait") => {
+		let result = waitup::wait_for_boot(bot, &ec2, &parsed_url_or_id, message, max_wait);
+		return waitup::say_boot_result(bot, &parsed_url_or_id, message, result);
+	    },
+	    Ok(()) => {},
+	    Err(_) if bot.should_stop() => return,
+	    Err(error) => {
+		let value = format!("Could not restart {} in {}: {}", &parsed_url_or_id, target.label(), error);
+		bot.say(&message.channel, &value, true);
+	    },
+	}
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Resize;
+
+
+impl Resize {
+    // Resizes the instance, first stopping it and waiting until it is stopped
+    // when `stop` is set.
+    pub fn resize(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard, parsed: (String, Target, Duration), stop: bool) {
+	let (parsed_url_or_id, target, max_wait) = parsed;
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let size = matches.value_of("size").unwrap_or(
+	    constants::RESIZE_INSTANCE
+	);
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "resize") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	if stop {
+	    bot.say(&message.channel, &format!("Stopping instance {} in {} to resize it", resolved.describe(), target.label()), true);
+	    match stop_and_wait(bot, message, &ec2, &resolved, max_wait) {
+		Ok(()) => {},
+		Err(_) if bot.should_stop() => return,
+		Err(error) => {
+		    let value = format!("Could not stop {} in {} to resize it: {}", &parsed_url_or_id, target.label(), error);
+		    return bot.say(&message.channel, &value, true);
+		},
+	    }
+	}
+	let value = match aws::resize_instance(&ec2, &resolved, size.to_owned()) {
+	    Ok(_) => format!(
+		"Resized instance {} in {} to {}: {}",
+		resolved.describe(),
+		target.label(),
+		&size,
+		resolve_instance(&ec2, resolved.id())
+		    .map(|resized| format!("{:?}", resized.instance))
+		    .unwrap_or_else(|error| error.to_string())
+	    ),
+	    Err(error) => {
+		let mut value = format!("Could not resize {} in {}: {}", &parsed_url_or_id, target.label(), error);
+		if !stop && resolved.instance.state() != "stopped" {
+		    value.push_str(&format!("\nThe instance is {}, use --stop to stop it first", resolved.instance.state()));
+		}
+		return bot.say(&message.channel, &value, true);
+	    },
+	};
+	bot.say(&message.channel, &value, true);
+	if matches.is_present("start") {
+	    let value = match aws::start_instance(&ec2, &resolved) {
+		Ok(_) => format!("Starting instance {} in {}", resolved.id(), target.label()),
+		Err(error) => format!("Could not start {} in {}: {}", &parsed_url_or_id, target.label(), error),
+	    };
+	    bot.say(&message.channel, &value, true);
+	}
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+impl Command for Resize {
+    fn name(&self) -> &'static str {
+	"resize"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE] -s/--size [SIZE] --stop --start"
+    }
+
+    fn about(&self) -> &'static str {
+	"Resize instance (default r5.2xlarge)"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 resize https://dev-84b292185-keenan.demo.encodedcc.org/",
+	    "ec2 resize i-02e86c27e5d31f8d1 --size c5.9xlarge",
+	    "ec2 resize i-02e86c27e5d31f8d1 --size c5.9xlarge --stop --start",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    url_or_id_arg(),
+	    Arg::with_name("size")
+		.long("size")
+		.short("s")
+		.takes_value(true)
+		.help("Instance type to resize to (default r5.2xlarge)"),
+	    Arg::with_name("stop")
+		.long("stop")
+		.help("Stop the instance and wait until it is stopped before resizing"),
+	    Arg::with_name("start")
+		.long("start")
+		.help("Start the instance again after resizing"),
+	    waitup::max_wait_arg(),
+	].into_iter().chain(target_args()).collect()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed = parse_url_or_id_and_target(bot, matches)
+	    .and_then(|(parsed_url_or_id, target)| Ok((parsed_url_or_id, target, waitup::parse_max_wait(matches)?)));
+	match parsed {
+	    Ok(parsed) => self.resize(bot, matches, message, parsed, matches.is_present("stop")),
+	    Err(error) => bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	}
+    }
+}
+
+
+pub struct Terminate;
+
+
+impl Terminate {
+    // Lists the EBS volumes deleted with the instance and those left behind.
+    fn describe_volumes(instance: &InstanceInfo) -> String {
+	let (deleted, kept): (Vec<_>, Vec<_>) = instance.volumes().iter()
+	    .partition(|volume| volume.delete_on_termination);
+	let list = |volumes: Vec<&aws::BlockDevice>| if volumes.is_empty() {
+	    "none".to_owned()
+	} else {
+	    volumes.iter()
+		.map(|volume| format!("{} ({})", volume.volume_id, volume.device))
+		.collect::<Vec<_>>()
+		.join(", ")
+	};
+	format!("EBS volumes deleted: {}\nEBS volumes kept: {}", list(deleted), list(kept))
+    }
+
+    fn refuse(bot: &RustyBot, message: &MessageStandard, resolved: &Resolved, target: &Target, reason: &str) {
+	bot.audit(message, "ec2 terminate", resolved.id(), &format!("refused: {}", reason));
+	bot.say(&message.channel, &format!("Refusing to terminate {} in {}: {}", resolved.describe(), target.label(), reason), true);
+    }
+}
+
+
+impl Command for Terminate {
+    fn name(&self) -> &'static str {
+	"terminate"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE] --confirm [INSTANCE_ID]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Terminate instance, asking for its ID as confirmation"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 terminate https://dev-84b292185-keenan.demo.encodedcc.org/",
+	    "ec2 terminate https://dev-84b292185-keenan.demo.encodedcc.org/ --confirm i-02e86c27e5d31f8d1",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    url_or_id_arg(),
+	    Arg::with_name("confirm")
+		.long("confirm")
+		.takes_value(true)
+		.help("ID of the instance, confirming it should be terminated")
+	].into_iter().chain(target_args()).collect()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let (parsed_url_or_id, target) = match parse_url_or_id_and_target(bot, matches) {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let ec2 = match connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	let resolved = match resolve_or_say(bot, message, &ec2, &parsed_url_or_id, &target, "terminate") {
+	    Some(resolved) => resolved,
+	    None => return,
+	};
+	match aws::has_termination_protection(&ec2, &resolved) {
+	    Ok(false) => {},
+	    Ok(true) => return Terminate::refuse(bot, message, &resolved, &target, "termination protection is enabled"),
+	    Err(error) => {
+		let value = format!("Could not check termination protection of {} in {}: {}", resolved.describe(), target.label(), error);
+		return bot.say(&message.channel, &value, true);
+	    },
+	}
+	if let Some(tag) = resolved.instance.protected_by(&AwsConfig::from_env().protected_tags) {
+	    return Terminate::refuse(bot, message, &resolved, &target, &format!("protected tag {}", tag));
+	}
+	let volumes = Terminate::describe_volumes(&resolved.instance);
+	match matches.value_of("confirm") {
+	    None => {
+		let value = format!(
+		    "About to terminate {} in {}\n{}\nRun `ec2 terminate {} --confirm {}` to proceed",
+		    resolved.describe(),
+		    target.label(),
+		    volumes,
+		    parsed_url_or_id,
+		    resolved.id()
+		);
+		return bot.say(&message.channel, &value, true);
+	    },
+	    Some(confirm) if confirm != resolved.id() => {
+		let error = format!("Confirmation `{}` does not match the instance ID {}", confirm, resolved.id());
+		return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error);
+	    },
+	    Some(_) => {},
+	}
+	let value = match aws::terminate_instance(&ec2, &resolved) {
+	    Ok(terminated_instance) => {
+		bot.audit(message, "ec2 terminate", resolved.id(), "terminated");
+		format!("Terminating instance {} in {}\n{}\n{:?}", resolved.describe(), target.label(), volumes, terminated_instance)
+	    },
+	    Err(error) => {
+		bot.audit(message, "ec2 terminate", resolved.id(), &format!("failed: {}", error));
+		format!("Could not terminate {} in {}: {}", &parsed_url_or_id, target.label(), error)
+	    },
+	};
+	bot.say(&message.channel, &value, true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Ls;
+
+
+impl Command for Ls {
+    fn name(&self) -> &'static str {
+	"ls"
+    }
+
+    fn usage(&self) -> &'static str {
+	"-f/--filter [KEY=VALUE] -l/--limit [NUM] --region [REGION,...]"
+    }
+
+    fn about(&self) -> &'static str {
+	"List instances with optional filters across configured regions"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "ec2 ls --filter instance-type=t2.micro --limit 5",
+	    "ec2 ls -f instance-type=t2.micro -f instance-state-name=running -l 3",
+	    "ec2 ls -f tag:Name=dev-84b292185-keenan -f tag:started_by=keenan",
+	    "ec2 ls --region us-east-1,us-west-2 --account production",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    Arg::with_name("filter")
+		.long("filter")
+		.short("f")
+		.takes_value(true)
+		.multiple(true)
+		.help("EC2 filter as KEY=VALUE, may be repeated"),
+	    Arg::with_name("limit")
+		.long("limit")
+		.short("l")
+		.takes_value(true)
+		.help("Number of instances to show (default 3)")
+	].into_iter().chain(target_args()).collect()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let mut filters = vec![];
+	for filter in matches.values_of("filter").unwrap_or_default() {
+	    match filter.split_once('=') {
+		Some((key, value)) if !key.is_empty() => filters.push((key.to_string(), value.to_string())),
+		_ => {
+		    let error = format!("Invalid filter `{}`, expected KEY=VALUE", filter);
+		    return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error);
+		}
+	    }
+	}
+	let limit = match matches.value_of("limit").unwrap_or("3").parse::<usize>() {
+	    Ok(limit) => limit,
+	    Err(_) => {
+		let error = format!("Invalid limit `{}`, expected a number", matches.value_of("limit").unwrap());
+		return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error);
+	    }
+	};
+	let targets = match parse_targets(matches, true) {
+	    Ok(targets) => targets,
+	    Err(error) => return bot.say_usage_error(&message.channel, &["ec2", self.name()], &error),
+	};
+	let results: Vec<(&Target, Result<Vec<InstanceInfo>, AwsError>)> = thread::scope(|scope| {
+	    let handles: Vec<_> = targets.iter().map(|target| {
+		let filters = filters.clone();
+		scope.spawn(move || {
+		    let matching_instances = make_ec2_client(target)
+			.and_then(|ec2| get_instance_info_from_filters(&ec2, filters));
+		    (target, matching_instances)
+		})
+	    }).collect();
+	    handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+	});
+	let mut instances = vec![];
+	let mut errors = vec![];
+	for (target, matching_instances) in results {
+	    match matching_instances {
+		Ok(matching_instances) => instances.extend(
+		    matching_instances.into_iter().map(|info| (target.label(), info))
+		),
+		Err(error) => errors.push(format!("Could not list instances in {}: {}", target.label(), error)),
+	    }
+	}
+	// Every page of every region has been read, so the total is exact.
+	let total = instances.len();
+	let mut lines = vec![format!("Showing {} out of {}:", total.min(limit), total)];
+	for (i, (label, info)) in instances.iter().take(limit).enumerate() {
+	    lines.push(format!("{} [{}] {:?}", i, label, info));
+	}
+	lines.extend(errors);
+	bot.say(&message.channel, &lines.join("\n"), true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,49 @@
+use crate::bot::RustyBot;
+use crate::registry::Command;
+use clap::{Arg, ArgMatches};
+use slack_api::MessageStandard;
+
+
+pub struct Help;
+
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+	"help"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[COMMAND]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Print this message or detailed usage of a command"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["help ec2 resize"]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    Arg::with_name("command")
+		.multiple(true)
+		.help("Command to describe, e.g. `kronitor` or `ec2 resize`")
+	]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let path = matches.values_of("command").unwrap_or_default().collect::<Vec<_>>();
+	if path.is_empty() {
+	    bot.say(&message.channel, &bot.registry().help(), false);
+	    return;
+	}
+	match bot.registry().command_help(&path) {
+	    Some(help) => bot.say(&message.channel, &help, false),
+	    None => {
+		let value = format!("Unknown command `{}`, try `help`", path.join(" "));
+		bot.say(&message.channel, &value, false);
+	    }
+	}
+    }
+}
@@ -0,0 +1,153 @@
+use crate::bot::RustyBot;
+use crate::constants;
+use crate::history::{self, CycleRecord};
+use crate::monitor::{self, IndexerEndpoint};
+use crate::progress::format_duration;
+use crate::registry::Command;
+use clap::{Arg, ArgMatches};
+use slack_api::MessageStandard;
+use std::error::Error;
+use std::{thread, time};
+
+
+fn indexer_arg() -> Arg<'static, 'static> {
+    Arg::with_name("indexer")
+	.long("indexer")
+	.takes_value(true)
+	.help("Indexer to report on: indexer, visindexer or regionindexer (default indexer)")
+}
+
+
+fn parse_indexer(matches: &ArgMatches) -> Result<IndexerEndpoint, Box<dyn Error>> {
+    match matches.value_of("indexer") {
+	Some(name) => match monitor::parse_indexers(name)?.as_slice() {
+	    [indexer] => Ok(*indexer),
+	    _ => Err(format!("Expected a single indexer, got `{}`", name).into()),
+	},
+	None => Ok(monitor::INDEXER),
+    }
+}
+
+
+pub struct History;
+
+
+impl Command for History {
+    fn name(&self) -> &'static str {
+	"history"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL] --indexer [NAME]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Show recorded indexing cycle times of URL"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "history https://encd-5745-a25e2d404-keenan.demo.encodedcc.org/",
+	    "history https://encd-5745-a25e2d404-keenan.demo.encodedcc.org/ --indexer visindexer",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    Arg::with_name("url")
+		.help("Demo or production URL"),
+	    indexer_arg(),
+	]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed = bot.get_url_value_and_parse(matches)
+	    .and_then(|parsed_url| Ok((parsed_url, parse_indexer(matches)?)));
+	let (parsed_url, indexer) = match parsed {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	let records = bot.history().load();
+	let cycles: Vec<&CycleRecord> = records.iter()
+	    .filter(|record| record.url == parsed_url && record.indexer == indexer.name)
+	    .collect();
+	let stats = history::deployment_stats(&records, indexer.name);
+	let current = match stats.iter().find(|stats| stats.url == parsed_url) {
+	    Some(current) => current,
+	    None => {
+		let value = format!("No {} cycles recorded for {}", indexer.name, &parsed_url);
+		return bot.say(&message.channel, &value, true);
+	    },
+	};
+	let mut lines = vec![format!("{} history for {} ({} cycles)", indexer.name, &parsed_url, cycles.len())];
+	for record in cycles.iter().rev().take(constants::HISTORY_SHOWN) {
+	    lines.push(format!("    {}", record.describe()));
+	}
+	lines.push(format!(
+	    "Average {}, longest {}",
+	    format_duration(current.average),
+	    format_duration(current.longest)
+	));
+	if let Some((difference, others)) = history::compare_to_baseline(current, &stats) {
+	    lines.push(format!("Longest cycle is {}", history::describe_comparison(difference, others)));
+	}
+	bot.say(&message.channel, &lines.join("\n"), true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
+
+
+pub struct Trend;
+
+
+impl Command for Trend {
+    fn name(&self) -> &'static str {
+	"trend"
+    }
+
+    fn usage(&self) -> &'static str {
+	"--indexer [NAME]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Compare cycle times of recently seen deployments and flag regressions"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["trend", "trend --indexer visindexer"]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![indexer_arg()]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let indexer = match parse_indexer(matches) {
+	    Ok(indexer) => indexer,
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	let records = bot.history().load();
+	let stats = history::deployment_stats(&records, indexer.name);
+	if stats.is_empty() {
+	    return bot.say(&message.channel, &format!("No {} cycles recorded yet", indexer.name), true);
+	}
+	let mut lines = vec![format!("{} cycle times of the last {} deployments", indexer.name, stats.len().min(constants::HISTORY_SHOWN))];
+	for (i, current) in stats.iter().take(constants::HISTORY_SHOWN).enumerate() {
+	    let mut line = format!(
+		"    {}: {} cycles, average {}, longest {}",
+		current.url,
+		current.cycles,
+		format_duration(current.average),
+		format_duration(current.longest)
+	    );
+	    // Each deployment is compared with the ones seen before it.
+	    let older = &stats[i + 1..];
+	    if let Some((difference, others)) = history::compare_to_baseline(current, older) {
+		line.push_str(&format!(", {}", history::describe_comparison(difference, others)));
+	    }
+	    lines.push(line);
+	}
+	bot.say(&message.channel, &lines.join("\n"), true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::bot::RustyBot;
+use crate::registry::Command;
+use clap::{Arg, ArgMatches};
+use slack_api::MessageStandard;
+
+
+// Jobs are owned by the connection, which answers well-formed `list` and
+// `cancel` messages before they reach a bot. These only see malformed input.
+pub struct List;
+
+
+impl Command for List {
+    fn name(&self) -> &'static str {
+	"list"
+    }
+
+    fn about(&self) -> &'static str {
+	"List active jobs"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["list"]
+    }
+
+    fn run(&self, bot: &RustyBot, _matches: &ArgMatches, message: &MessageStandard) {
+	bot.say_usage_error(&message.channel, &[self.name()], &"`list` takes no arguments");
+    }
+}
+
+
+pub struct Cancel;
+
+
+impl Command for Cancel {
+    fn name(&self) -> &'static str {
+	"stop"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+	vec!["cancel"]
+    }
+
+    fn usage(&self) -> &'static str {
+	"[JOB_ID]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Cancel job"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["stop 1234"]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    Arg::with_name("job_id")
+		.help("Job ID shown by `list` or in job messages")
+	]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let error = match matches.value_of("job_id") {
+	    Some(job_id) => format!("Invalid job ID `{}`", job_id),
+	    None => "Missing job ID".to_owned(),
+	};
+	bot.say_usage_error(&message.channel, &[self.name()], &error);
+    }
+}
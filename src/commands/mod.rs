@@ -0,0 +1,41 @@
+use crate::constants;
+use crate::registry::{self, Registry};
+use std::env;
+
+pub mod compare;
+pub mod ec2;
+pub mod help;
+pub mod history;
+pub mod jobs;
+pub mod monitor;
+pub mod status;
+pub mod waitup;
+
+
+pub fn make_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register(Box::new(help::Help));
+    registry.register(Box::new(jobs::List));
+    registry.register(Box::new(jobs::Cancel));
+    registry.register(Box::new(monitor::Monitor));
+    registry.register(Box::new(monitor::Vonitor));
+    registry.register(Box::new(monitor::Ronitor));
+    registry.register(Box::new(monitor::Konitor));
+    registry.register(Box::new(monitor::Kronitor));
+    registry.register(Box::new(monitor::Watch));
+    registry.register(Box::new(status::Status));
+    registry.register(Box::new(compare::Compare));
+    registry.register(Box::new(history::History));
+    registry.register(Box::new(history::Trend));
+    registry.register(Box::new(waitup::Waitup));
+    registry.register(Box::new(ec2::Ec2::new()));
+    for (alias, expansion) in constants::DEFAULT_ALIASES.iter() {
+	registry.add_alias(alias, expansion).unwrap();
+    }
+    for (alias, expansion) in registry::parse_aliases(&env::var("RUSTY_BOT_ALIASES").unwrap_or_default()) {
+	if let Err(error) = registry.add_alias(&alias, &expansion) {
+	    println!("Ignoring RUSTY_BOT_ALIASES entry: {}", error);
+	}
+    }
+    registry
+}
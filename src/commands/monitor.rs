@@ -0,0 +1,356 @@
+use crate::bot::RustyBot;
+use crate::commands::{ec2, waitup};
+use crate::constants;
+use crate::monitor::{self, MonitorOptions};
+use crate::registry::Command;
+use clap::{Arg, ArgMatches};
+use slack_api::MessageStandard;
+use std::error::Error;
+
+
+fn url_arg() -> Arg<'static, 'static> {
+    Arg::with_name("url")
+	.help("Demo or production URL")
+}
+
+
+fn urls_arg() -> Arg<'static, 'static> {
+    url_arg()
+	.multiple(true)
+	.help("Demo or production URLs, monitored at the same time")
+}
+
+
+fn size_arg() -> Arg<'static, 'static> {
+    Arg::with_name("size")
+	.long("size")
+	.short("s")
+	.takes_value(true)
+	.help("Instance type to resize to (default r5.2xlarge)")
+}
+
+
+fn on_error_arg() -> Arg<'static, 'static> {
+    Arg::with_name("on_error")
+	.long("on-error")
+	.takes_value(true)
+	.possible_values(&["continue", "abort"])
+	.help("Keep monitoring or abort when the indexer reports errors (default continue)")
+}
+
+
+// Polling flags parsed by `MonitorOptions::from_matches`.
+fn polling_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+	Arg::with_name("interval")
+	    .long("interval")
+	    .takes_value(true)
+	    .help("Time between polls, e.g. 30s (default 5s)"),
+	Arg::with_name("settle")
+	    .long("settle")
+	    .takes_value(true)
+	    .help("Consecutive polls matching --until before indexing counts as done (default 13)"),
+	Arg::with_name("initial_delay")
+	    .long("initial-delay")
+	    .takes_value(true)
+	    .help("Time to wait before the first poll, e.g. 2m (default 0s, 60s for the vis_indexer and region_indexer)"),
+	Arg::with_name("until")
+	    .long("until")
+	    .takes_value(true)
+	    .help("Condition on the JSON response that means done (default `status == waiting`)"),
+	Arg::with_name("timeout")
+	    .long("timeout")
+	    .takes_value(true)
+	    .help("Time limit for each request, e.g. 30s (default 10s)"),
+	Arg::with_name("retries")
+	    .long("retries")
+	    .takes_value(true)
+	    .help("Retries with backoff for timeouts, connection errors and 5xx responses (default 2)"),
+	Arg::with_name("max_failures")
+	    .long("max-failures")
+	    .takes_value(true)
+	    .help("Consecutive failed polls before aborting (default 3)"),
+    ]
+}
+
+
+fn monitor_args() -> Vec<Arg<'static, 'static>> {
+    [vec![on_error_arg()], polling_args()].concat()
+}
+
+
+// Flags for monitors that wait on several indexers before acting.
+fn indexers_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+	Arg::with_name("indexers")
+	    .long("indexers")
+	    .takes_value(true)
+	    .help("Comma separated indexers to wait on: indexer, visindexer, regionindexer (default indexer,visindexer)"),
+	Arg::with_name("parallel")
+	    .long("parallel")
+	    .help("Wait on the indexers at the same time instead of one after the other"),
+    ]
+}
+
+
+fn parse_urls_and_options(bot: &RustyBot, matches: &ArgMatches) -> Result<(Vec<String>, MonitorOptions), Box<dyn Error>> {
+    let parsed_urls = bot.get_url_values_and_parse(matches)?;
+    let options = MonitorOptions::from_matches(matches)?;
+    Ok((parsed_urls, options))
+}
+
+
+fn parse_url_and_options(bot: &RustyBot, matches: &ArgMatches) -> Result<(String, MonitorOptions), Box<dyn Error>> {
+    let parsed_url = bot.get_url_value_and_parse(matches)?;
+    let options = MonitorOptions::from_matches(matches)?;
+    Ok((parsed_url, options))
+}
+
+
+pub struct Monitor;
+
+
+impl Command for Monitor {
+    fn name(&self) -> &'static str {
+	"monitor"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL...] [OPTIONS]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Monitor URL until indexing is complete"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "monitor https://test.encodedcc.org/",
+	    "monitor https://test.encodedcc.org/ https://www.encodeproject.org/",
+	    "monitor https://test.encodedcc.org/ --on-error abort",
+	    "monitor https://test.encodedcc.org/ --interval 30s --settle 4 --until 'status == waiting && results[0].invalidated == 0'",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![urls_arg()], monitor_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	match parse_urls_and_options(bot, matches) {
+	    Ok((parsed_urls, options)) => {
+		let _ = bot.poll_indexer_urls(&monitor::INDEXER, &parsed_urls, message, &options);
+	    },
+	    Err(error) => bot.say_usage_error(&message.channel, &[self.name()], &error),
+	}
+    }
+}
+
+
+pub struct Vonitor;
+
+
+impl Command for Vonitor {
+    fn name(&self) -> &'static str {
+	"vonitor"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL...] [OPTIONS]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Monitor URL until visindexing is complete"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "vonitor https://encd-5745-a25e2d404-keenan.demo.encodedcc.org/",
+	    "vonitor https://encd-5745-a25e2d404-keenan.demo.encodedcc.org/ --initial-delay 0s",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![urls_arg()], monitor_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	match parse_urls_and_options(bot, matches) {
+	    Ok((parsed_urls, options)) => {
+		let _ = bot.poll_indexer_urls(&monitor::VISINDEXER, &parsed_urls, message, &options);
+	    },
+	    Err(error) => bot.say_usage_error(&message.channel, &[self.name()], &error),
+	}
+    }
+}
+
+
+pub struct Ronitor;
+
+
+impl Command for Ronitor {
+    fn name(&self) -> &'static str {
+	"ronitor"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL...] [OPTIONS]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Monitor URL until region indexing is complete"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["ronitor https://encd-5745-a25e2d404-keenan.demo.encodedcc.org/"]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![urls_arg()], monitor_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	match parse_urls_and_options(bot, matches) {
+	    Ok((parsed_urls, options)) => {
+		let _ = bot.poll_indexer_urls(&monitor::REGIONINDEXER, &parsed_urls, message, &options);
+	    },
+	    Err(error) => bot.say_usage_error(&message.channel, &[self.name()], &error),
+	}
+    }
+}
+
+
+pub struct Konitor;
+
+
+impl Command for Konitor {
+    fn name(&self) -> &'static str {
+	"konitor"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL] [OPTIONS]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Monitor URL until indexing is complete and stop instance"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "konitor https://sno-158-2dd8b5924-keenan.demo.encodedcc.org/",
+	    "konitor https://sno-158-2dd8b5924-keenan.demo.encodedcc.org/ --indexers indexer,visindexer,regionindexer --parallel",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![url_arg()], monitor_args(), indexers_args(), ec2::target_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let (parsed_url, options) = match parse_url_and_options(bot, matches) {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	if bot.poll_indexers(&parsed_url, message, &options).is_err() {
+	    return;
+	}
+	if bot.should_stop() {
+	    println!{"Cancelling"};
+	    return;
+	}
+	ec2::Stop.run(bot, matches, message);
+    }
+}
+
+
+pub struct Kronitor;
+
+
+impl Command for Kronitor {
+    fn name(&self) -> &'static str {
+	"kronitor"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL] -s/--size [SIZE] [OPTIONS]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Monitor URL until indexing is complete, stop and resize instance (default r5.2xlarge)"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "kronitor https://dev-84b292185-keenan.demo.encodedcc.org/",
+	    "kronitor https://dev-84b292185-keenan.demo.encodedcc.org/ -s c5.4xlarge",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	[vec![url_arg(), size_arg(), waitup::max_wait_arg()], monitor_args(), indexers_args(), ec2::target_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	// Checked before polling so a bad option is not reported hours later.
+	let parsed = parse_url_and_options(bot, matches)
+	    .and_then(|(parsed_url, options)| Ok((parsed_url, options, ec2::parse_target(matches)?, waitup::parse_max_wait(matches)?)));
+	let (parsed_url, options, target, max_wait) = match parsed {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	if bot.poll_indexers(&parsed_url, message, &options).is_err() {
+	    return;
+	}
+	if bot.should_stop() {
+	    println!{"Cancelling"};
+	    return;
+	}
+	// The instance has to be stopped before it can be resized.
+	ec2::Resize.resize(bot, matches, message, (parsed_url, target, max_wait), true);
+    }
+}
+
+
+pub struct Watch;
+
+
+impl Command for Watch {
+    fn name(&self) -> &'static str {
+	"watch"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL] --path [PATH] --until [CONDITION] [OPTIONS]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Poll a JSON endpoint of URL until a condition holds"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "watch https://test.encodedcc.org/ --path /_indexer --until 'status == \"waiting\"'",
+	    "watch https://test.encodedcc.org/ --path /_health --until 'status == ok' --settle 1",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	let path = Arg::with_name("path")
+	    .long("path")
+	    .takes_value(true)
+	    .default_value(constants::INDEXER_PATH)
+	    .help("Endpoint to poll");
+	[vec![url_arg(), path], polling_args()].concat()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	match parse_url_and_options(bot, matches) {
+	    Ok((parsed_url, options)) => {
+		let path = matches.value_of("path").unwrap_or(constants::INDEXER_PATH);
+		let _ = bot.watch(parsed_url, path, message, &options);
+	    },
+	    Err(error) => bot.say_usage_error(&message.channel, &[self.name()], &error),
+	}
+    }
+}
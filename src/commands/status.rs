@@ -0,0 +1,53 @@
+use crate::bot::{self, RustyBot};
+use crate::constants;
+use crate::http::HttpOptions;
+use crate::monitor;
+use crate::registry::Command;
+use clap::{Arg, ArgMatches};
+use slack_api::MessageStandard;
+use std::{thread, time};
+
+
+pub struct Status;
+
+
+impl Command for Status {
+    fn name(&self) -> &'static str {
+	"status"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[URL]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Return URL indexer status and results"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec!["status https://www.encodeproject.org/"]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    Arg::with_name("url")
+		.help("Demo or production URL")
+	]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed_url = match bot.get_url_value_and_parse(matches) {
+	    Ok(parsed_url) => parsed_url,
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	let value = match bot::get_indexer(&parsed_url, constants::INDEXER_PATH, &HttpOptions::from_env()) {
+	    Ok(result) => {
+		bot.record_cycles(&parsed_url, &monitor::INDEXER, &result);
+		format!("Indexer status for {}\n{}", &parsed_url, result.summary())
+	    },
+	    Err(error) => format!("Could not get indexer status for {}: {}", &parsed_url, error),
+	};
+	bot.say(&message.channel, &value, true);
+	thread::sleep(time::Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::aws::{self, AwsError};
+use crate::bot::RustyBot;
+use crate::commands::ec2;
+use crate::constants;
+use crate::http::{self, HttpOptions};
+use crate::monitor::parse_seconds;
+use crate::progress::format_duration;
+use crate::registry::Command;
+use chrono::{DateTime, Duration, Utc};
+use clap::{Arg, ArgMatches};
+use reqwest::StatusCode;
+use rusoto_ec2::Ec2Client;
+use slack_api::MessageStandard;
+use std::error::Error;
+
+
+pub fn max_wait_arg() -> Arg<'static, 'static> {
+    Arg::with_name("max_wait")
+	.long("max-wait")
+	.takes_value(true)
+	.help("Time to wait before giving up, e.g. 20m (default 15m)")
+}
+
+
+pub fn parse_max_wait(matches: &ArgMatches) -> Result<Duration, Box<dyn Error>> {
+    match matches.value_of("max_wait") {
+	Some(max_wait) => parse_seconds(max_wait)
+	    .filter(|max_wait| *max_wait > Duration::zero())
+	    .ok_or_else(|| format!("Invalid max wait `{}`, expected e.g. 20m", max_wait).into()),
+	None => Ok(Duration::seconds(constants::DEFAULT_MAX_WAIT)),
+    }
+}
+
+
+// Sleeps until the next check. Fails with `last_error` once `deadline` passes.
+fn wait_for_next_check(bot: &RustyBot, started: DateTime<Utc>, deadline: DateTime<Utc>, last_error: &str) -> Result<(), Box<dyn Error>> {
+    let now = Utc::now();
+    if now >= deadline {
+	return Err(format!("Timed out after {}, last error: {}", format_duration(now - started), last_error).into());
+    }
+    let next_check = (now + Duration::seconds(constants::WAITUP_INTERVAL)).min(deadline);
+    if !bot.wait_until(next_check) {
+	return Err("Cancelling".into());
+    }
+    Ok(())
+}
+
+
+// Waits for the instance behind `url_or_id` to reach running and then for
+// its URL to answer with HTTP 200. The URL is `url_or_id` itself or the demo
+// URL of the instance's `Name` tag. Returns the boot duration.
+pub fn wait_for_boot(bot: &RustyBot, ec2: &Ec2Client, url_or_id: &str, message: &MessageStandard, max_wait: Duration) -> Result<Duration, Box<dyn Error>> {
+    let started = Utc::now();
+    let deadline = started.checked_add_signed(max_wait).ok_or("Max wait is too long")?;
+    let is_url = url_or_id.starts_with("http");
+    let mut url = if is_url { Some(url_or_id.to_owned()) } else { None };
+    loop {
+	let last_error = match aws::resolve_instance(ec2, url_or_id) {
+	    Ok(resolved) if resolved.instance.state() == "running" => {
+		let value = format!("Instance for {} is running after {}", url_or_id, format_duration(Utc::now() - started));
+		bot.say_in_thread(message, &value, true);
+		url = url.or_else(|| resolved.instance.demo_url());
+		break;
+	    },
+	    Ok(resolved) if resolved.instance.state() == "shutting-down" || resolved.instance.state() == "terminated" => {
+		return Err(format!("instance is {}", resolved.instance.state()).into());
+	    },
+	    Ok(resolved) => format!("instance is {}", resolved.instance.state()),
+	    Err(error @ AwsError::NotFound(_)) if is_url => {
+		bot.say_in_thread(message, &format!("{}, waiting for the URL only", error), true);
+		break;
+	    },
+	    Err(error @ AwsError::Ambiguous(..)) => return Err(error.into()),
+	    Err(error) => error.to_string(),
+	};
+	wait_for_next_check(bot, started, deadline, &last_error)?;
+    }
+    let url = match url {
+	Some(url) => url,
+	None => {
+	    let value = format!("{} has no demo URL in its Name tag, only its EC2 state was checked", url_or_id);
+	    bot.say_in_thread(message, &value, true);
+	    return Ok(Utc::now() - started);
+	},
+    };
+    if !is_url {
+	bot.say_in_thread(message, &format!("Waiting for {} to answer", url), true);
+    }
+    let options = HttpOptions::from_env();
+    loop {
+	let last_error = match http::get_status(&url, &options) {
+	    Ok(status) if status == StatusCode::OK => return Ok(Utc::now() - started),
+	    Ok(status) => format!("HTTP {}", status),
+	    Err(error) => error.to_string(),
+	};
+	wait_for_next_check(bot, started, deadline, &last_error)?;
+    }
+}
+
+
+// Waits for the instance `instance_id` to be in `state`, reporting each
+// state it goes through in the thread of `message`.
+pub fn wait_for_state(bot: &RustyBot, ec2: &Ec2Client, instance_id: &str, state: &str, message: &MessageStandard, max_wait: Duration) -> Result<Duration, AwsError> {
+    aws::wait_for_state(
+	ec2,
+	instance_id,
+	state,
+	max_wait,
+	|next_check| bot.wait_until(next_check),
+	|current, took| bot.say_in_thread(message, &format!("Instance {} is {} after {}", instance_id, current, format_duration(took)), true)
+    )
+}
+
+
+// Posts the outcome of `wait_for_boot`, staying quiet when the job was cancelled.
+pub fn say_boot_result(bot: &RustyBot, url_or_id: &str, message: &MessageStandard, result: Result<Duration, Box<dyn Error>>) {
+    let value = match result {
+	Ok(took) => format!("UP {} after {}", url_or_id, format_duration(took)),
+	Err(_) if bot.should_stop() => return,
+	Err(error) => format!("Could not bring up {}: {}", url_or_id, error),
+    };
+    bot.say(&message.channel, &value, true);
+}
+
+
+pub struct Waitup;
+
+
+impl Command for Waitup {
+    fn name(&self) -> &'static str {
+	"waitup"
+    }
+
+    fn usage(&self) -> &'static str {
+	"[INSTANCE] --max-wait [DURATION] --region [REGION]"
+    }
+
+    fn about(&self) -> &'static str {
+	"Wait until the instance is running and URL answers"
+    }
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![
+	    "waitup https://dev-84b292185-keenan.demo.encodedcc.org/",
+	    "waitup https://dev-84b292185-keenan.demo.encodedcc.org/ --max-wait 30m",
+	]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![
+	    ec2::url_or_id_arg(),
+	    max_wait_arg(),
+	].into_iter().chain(ec2::target_args()).collect()
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	let parsed = bot.get_url_or_id_value_and_parse(matches)
+	    .and_then(|parsed_url_or_id| Ok((parsed_url_or_id, parse_max_wait(matches)?, ec2::parse_target(matches)?)));
+	let (parsed_url_or_id, max_wait, target) = match parsed {
+	    Ok(parsed) => parsed,
+	    Err(error) => return bot.say_usage_error(&message.channel, &[self.name()], &error),
+	};
+	let ec2 = match ec2::connect(bot, message, &target) {
+	    Some(ec2) => ec2,
+	    None => return,
+	};
+	bot.say(&message.channel, &format!("START waiting for {}", &parsed_url_or_id), true);
+	let result = wait_for_boot(bot, &ec2, &parsed_url_or_id, message, max_wait);
+	say_boot_result(bot, &parsed_url_or_id, message, result);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::make_registry;
+
+
+    fn parse_max_wait_of(max_wait: &str) -> Result<Duration, Box<dyn Error>> {
+	let matches = make_registry().make_app().get_matches_from_safe(
+	    vec!["<@rustybot>", "waitup", "i-02e86c27e5d31f8d1", "--max-wait", max_wait]
+	).unwrap();
+	parse_max_wait(matches.subcommand_matches("waitup").unwrap())
+    }
+
+
+    #[test]
+    fn test_parse_max_wait() {
+	assert_eq!(parse_max_wait_of("20m").unwrap(), Duration::minutes(20));
+	assert!(parse_max_wait_of("0").is_err());
+	assert!(parse_max_wait_of("9999999999999d").is_err());
+	assert!(parse_max_wait_of("9223372036854775807").is_err());
+    }
+}
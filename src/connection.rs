@@ -1,26 +1,44 @@
+use crate::audit::Audit;
 use crate::bot;
+use crate::commands;
+use crate::history::History;
+use crate::registry::Registry;
 use crate::constants::{self, Worker, Workers};
+use crate::schedule::{self, Schedule, ScheduledJob};
+use chrono::{DateTime, Local, Utc};
 use slack::{self, Event, RtmClient, Message};
 use std::thread;
 use slack_api::{self, MessageStandard};
 use crossbeam_channel::{unbounded, Sender, Receiver};
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
 use std::sync::Arc;
-use regex::Regex;
-use lazy_static;
+use crate::tokenize::tokenize;
+
+
+static COUNTER: AtomicUsize = AtomicUsize::new(1000);
 
 
 fn get_worker_id() -> usize {
-    static COUNTER:AtomicUsize = AtomicUsize::new(1000);
     COUNTER.fetch_add(1, Ordering::Relaxed)
 }
 
 
+fn reserve_worker_id(worker_id: &str) {
+    if let Ok(worker_id) = worker_id.parse::<usize>() {
+	COUNTER.fetch_max(worker_id + 1, Ordering::Relaxed);
+    }
+}
+
+
 pub struct Connection {
     token: String,
     tx: Sender<String>,
     rx: Receiver<String>,
-    workers: Workers
+    workers: Workers,
+    schedule: Arc<Schedule>,
+    registry: Arc<Registry>,
+    history: Arc<History>,
+    audit: Arc<Audit>
 }
 
 
@@ -28,12 +46,18 @@ impl Connection {
 
     pub fn new(token: &str) -> Self {
 	let (tx, rx) = unbounded();
-	Connection {
+	let mut connection = Connection {
 	    token: token.to_string(),
-	    tx: tx,
-	    rx: rx,
-	    workers: vec![]
-	}
+	    tx,
+	    rx,
+	    workers: vec![],
+	    schedule: Arc::new(Schedule::from_env()),
+	    registry: Arc::new(commands::make_registry()),
+	    history: Arc::new(History::from_env()),
+	    audit: Arc::new(Audit::from_env())
+	};
+	connection.restore_scheduled_jobs();
+	connection
     }
     
     pub fn listen(&mut self,) -> Result<(), slack::error::Error> {
@@ -48,7 +72,7 @@ impl Connection {
 
     fn maybe_get_message_from_event<'a>(&self, event: &'a Event) -> Option<&'a Message> {
         match event {
-	    Event::Message(message) => Some(&message),
+	    Event::Message(message) => Some(message),
      	    _ => None
         }
     }
@@ -63,23 +87,12 @@ impl Connection {
 	self.workers.push(worker);
     }
 
-    fn remove_multiple_spaces(&self, message_text: String) -> String {
-	lazy_static! {
-            static ref MULTIPLE_SPACES: Regex = Regex::new(
-		r"\s\s+"
-            ).unwrap();
-	}
-	MULTIPLE_SPACES.replace_all(&message_text, " ").to_string()
-    }
-
     fn remove_nonbreaking_space(&self, message_text: String) -> String {
 	message_text.replace("\u{a0}", " ").to_string()
     }
 
     fn clean_message_text(&self, message_text: String) -> String {
-	self.remove_multiple_spaces(
-	    self.remove_nonbreaking_space(message_text)
-	)
+	self.remove_nonbreaking_space(message_text)
     }
 
     fn clean_slack_message(&self, message: &MessageStandard) -> MessageStandard {
@@ -102,6 +115,9 @@ impl Connection {
 	    worker_id_clone,
 	    self.tx.clone(),
 	    is_cancelled.clone(),
+	    self.registry.clone(),
+	    self.history.clone(),
+	    self.audit.clone(),
 	);
         let handle = thread::spawn(
 	    move || {
@@ -111,19 +127,83 @@ impl Connection {
 	self.register_bot((worker_id, handle, is_cancelled, message_text));
     }
 
+    fn spawn_scheduled_thread(&mut self, job: ScheduledJob) {
+	reserve_worker_id(&job.worker_id);
+	let is_cancelled = Arc::new(AtomicBool::new(false));
+	let rustybot = bot::RustyBot::new(
+	    self.token.clone(),
+	    job.worker_id.clone(),
+	    self.tx.clone(),
+	    is_cancelled.clone(),
+	    self.registry.clone(),
+	    self.history.clone(),
+	    self.audit.clone(),
+	);
+	let schedule = self.schedule.clone();
+	let worker_id = job.worker_id.clone();
+	let description = job.describe();
+	let handle = thread::spawn(
+	    move || {
+		if !rustybot.wait_until(job.fire_at) {
+		    return;
+		}
+		schedule.remove(&job.worker_id);
+		if job.is_missed(Utc::now()) {
+		    let value = format!(
+			"Not running `{}`, it was due at {} while the bot was down",
+			job.command(),
+			schedule::format_fire_at(&job.fire_at)
+		    );
+		    return rustybot.say(&Some(job.channel.clone()), &value, true);
+		}
+		rustybot.handle_message(job.to_message());
+	    }
+	);
+	self.register_bot((worker_id, handle, is_cancelled, description));
+    }
+
+    fn restore_scheduled_jobs(&mut self) {
+	for job in self.schedule.load() {
+	    println!("Restoring scheduled job {}", &job.worker_id);
+	    self.spawn_scheduled_thread(job);
+	}
+    }
+
+    fn schedule_message(&mut self, message: &MessageStandard, fire_at: DateTime<Utc>, command: String, cli: &RtmClient) {
+	let job = ScheduledJob {
+	    worker_id: get_worker_id().to_string(),
+	    fire_at,
+	    channel: message.channel.clone().unwrap(),
+	    text: format!("{} {}", constants::BOT_ID, command),
+	    user: message.user.clone()
+	};
+	let _ = cli.sender().send_message(
+	    &job.channel,
+	    &format!(
+		"Scheduled `{}` for {} [JOB {}]",
+		command,
+		schedule::format_fire_at(&fire_at),
+		&job.worker_id
+	    )
+	);
+	self.schedule.add(job.clone());
+	self.spawn_scheduled_thread(job);
+    }
+
     fn cancel_bot_by_worker_id(&mut self, worker_id: &String, channel: &Option<String>, cli: &RtmClient) {
 	for bot in self.workers.iter() {
     	    if &bot.0 == worker_id {
-		cli.sender().send_message(
-		    &channel.as_ref().unwrap(),
+		let _ = cli.sender().send_message(
+		    channel.as_ref().unwrap(),
 		    &format!("Canceling {}", worker_id)
 		);
 	        bot.2.store(true, Ordering::Relaxed);
+		self.schedule.remove(worker_id);
 		return;
 	    }
 	}
-	cli.sender().send_message(
-	    &channel.as_ref().unwrap(),
+	let _ = cli.sender().send_message(
+	    channel.as_ref().unwrap(),
 	    &format!("No active job {} found", worker_id)
 	);
     }
@@ -147,33 +227,32 @@ impl Connection {
 	    if let Some(bot) = self.pop_bot_by_worker_id(&worker_id) {
 		println!("Joining {:?}", &bot.0);
 	        bot.1.join().unwrap_or_else(
-		    |x| println!("Error joining")
+		    |_| println!("Error joining")
 		);
 	    }
 	};
     }
     
-    fn should_cancel_job(&self, text: &Option<String>) -> Option<String> {
-	lazy_static! {
-            static ref JOB_RE: Regex = Regex::new(r"(cancel|stop) (\d+)").unwrap();
-	}
+    fn tokenize_bot_command(&self, text: &Option<String>) -> Vec<String> {
 	if let Some(message) = text {
-	    if message.starts_with(constants::BOT_ID) {
-		if let Some(capture) = JOB_RE.captures(message) {
-		    return Some(capture.get(2).unwrap().as_str().to_owned());
-		}
+	    if let Some(command) = message.strip_prefix(constants::BOT_ID) {
+		return self.registry.expand_alias(tokenize(command).unwrap_or_default());
 	    }
 	}
-	None
+	vec![]
     }
 
-    fn should_list_active_jobs(&self, text: &Option<String>) -> bool {
-	if let Some(message) = text {
-	    if message.trim_end() == format!("{} list", constants::BOT_ID) {
-		return true
-	    }
+    fn should_cancel_job(&self, text: &Option<String>) -> Option<String> {
+	match self.tokenize_bot_command(text).as_slice() {
+	    [command, worker_id] if (command == "cancel" || command == "stop")
+		&& !worker_id.is_empty()
+		&& worker_id.chars().all(|c| c.is_ascii_digit()) => Some(worker_id.to_owned()),
+	    _ => None
 	}
-	false
+    }
+
+    fn should_list_active_jobs(&self, text: &Option<String>) -> bool {
+	self.tokenize_bot_command(text) == ["list"]
     }
 
     fn list_jobs(&mut self, channel: &Option<String>, cli: &RtmClient) {
@@ -182,7 +261,16 @@ impl Connection {
 		(bot.0.to_owned(), bot.3.to_owned())
 	    }
 	).collect();
-	cli.sender().send_message(&channel.as_ref().unwrap(), &format!("{:?}", jobs));
+	let _ = cli.sender().send_message(channel.as_ref().unwrap(), &format!("{:?}", jobs));
+    }
+
+    fn should_schedule_message(&self, text: &Option<String>) -> Option<(DateTime<Utc>, String)> {
+	if let Some(message) = text {
+	    if let Some(command) = message.strip_prefix(constants::BOT_ID) {
+		return schedule::parse_delay(command, Local::now());
+	    }
+	}
+	None
     }
 
     fn should_pass_message_to_bot(&self, text: &Option<String>) -> bool {
@@ -199,6 +287,8 @@ impl Connection {
 	    self.cancel_bot_by_worker_id(&worker_id, &message.channel, cli);
 	} else if self.should_list_active_jobs(&message.text) {
 	    self.list_jobs(&message.channel, cli);
+	} else if let Some((fire_at, command)) = self.should_schedule_message(&message.text) {
+	    self.schedule_message(message, fire_at, command, cli);
 	} else if self.should_pass_message_to_bot(&message.text) {
 	    self.spawn_thread(message.to_owned());
 	}
@@ -209,20 +299,17 @@ impl Connection {
 impl slack::EventHandler for Connection {
     fn on_event(&mut self, cli: &RtmClient, event: Event) {
         let maybe_message = self.maybe_get_message_from_event(&event);
-	match maybe_message {
-	    Some(Message::Standard(message)) => {
-		self.handle_message(cli, &self.clean_slack_message(message));
-	    },
-	    _ => {}
+	if let Some(Message::Standard(message)) = maybe_message {
+	    self.handle_message(cli, &self.clean_slack_message(message));
 	}
 	self.join_completed_threads();
     }
 
-    fn on_close(&mut self, cli: &RtmClient) {
+    fn on_close(&mut self, _cli: &RtmClient) {
         println!("Closing!");
     }
 
-    fn on_connect(&mut self, cli: &RtmClient) {
+    fn on_connect(&mut self, _cli: &RtmClient) {
         println!("Connected!");
     }
 }
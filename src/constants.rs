@@ -9,53 +9,56 @@ pub type Workers = Vec<Worker>;
 
 pub const BOT_ID: &str = "<@U013X667NR4>";
 
-pub const EMOJIS: [&str; 7] = [
-    ":hugging_face:",
-    ":lion_face:",
-    ":see_no_evil:",
-    ":duck:",
-    ":palm_tree:",
-    ":microscope:",
-    ":man-surfing:"
-];
+pub const BOT_NAME: &str = "<@rustybot>";
+
+pub const DELAY_HELP: &str = r#"    *in [DURATION] [COMMAND]*     Run command after a delay (e.g. 45s, 90m, 2h, 1h30m)
+    *at [HH:MM] [COMMAND]*     Run command at the next HH:MM
+"#;
 
-pub const HELP: &str = r#"
-USAGE:
-    <@rustybot> [COMMAND]
-COMMANDS:
-    *help*     Print this message
-    *list*     List active jobs
-    *stop/cancel [JOB_ID]*     Cancel job
-    *monitor [URL]*     Monitor URL until indexing is complete
-    *vonitor [URL]*     Monitor URL until visindexing is complete
-    *konitor [URL]*     Monitor URL until indexing is complete and stop instance
-    *kronitor [URL] -s/--size [SIZE]*     Monitor URL until indexing is complete, stop and resize instance (default r5.2xlarge)
-    *status [URL]*     Return URL indexer status and results
-    *ec2 info [URL/ID]*    Get instance info
-    *ec2 start [URL/ID]*    Start instance
-    *ec2 stop [URL/ID]*    Stop instance
-    *ec2 resize [URL/ID] -s/--size [SIZE]*     Resize instance (default r5.2xlarge)
-    *ec2 ls -f/--filter [KEY=VALUE] -l/--limit [NUM]*     List instances with optional filters
-EXAMPLES:
-    <@rustybot> list
-    <@rustybot> stop 1234
-    <@rustybot> status https://www.encodeproject.org/
-    <@rustybot> monitor https://test.encodedcc.org/
-    <@rustybot> vonitor https://encd-5745-a25e2d404-keenan.demo.encodedcc.org/
-    <@rustybot> konitor https://sno-158-2dd8b5924-keenan.demo.encodedcc.org/
-    <@rustybot> kronitor https://dev-84b292185-keenan.demo.encodedcc.org/
-    <@rustybot> kronitor https://dev-84b292185-keenan.demo.encodedcc.org/ -s c5.4xlarge
-    <@rustybot> ec2 info https://dev-84b292185-keenan.demo.encodedcc.org/
-    <@rustybot> ec2 info i-02e86c27e5d31f8d1
-    <@rustybot> ec2 start i-02e86c27e5d31f8d1
-    <@rustybot> ec2 stop https://dev-84b292185-keenan.demo.encodedcc.org/
-    <@rustybot> ec2 resize https://dev-84b292185-keenan.demo.encodedcc.org/
-    <@rustybot> ec2 resize i-02e86c27e5d31f8d1 --size c5.9xlarge
-    <@rustybot> ec2 ls --filter instance-type=t2.micro --limit 5
-    <@rustybot> ec2 ls -f instance-type=t2.micro -f instance-state-name=running -l 3
-    <@rustybot> ec2 ls -f tag:Name=dev-84b292185-keenan -f tag:started_by=keenan
+pub const DELAY_EXAMPLES: &str = r#"    <@rustybot> in 90m ec2 stop https://dev-84b292185-keenan.demo.encodedcc.org/
+    <@rustybot> at 18:30 kronitor https://dev-84b292185-keenan.demo.encodedcc.org/
 "#;
 
+pub const DEFAULT_ALIASES: [(&str, &str); 1] = [
+    ("kill", "cancel")
+];
+
+pub const MAX_SUGGESTION_DISTANCE: usize = 2;
+
 pub const RESIZE_INSTANCE: &str = "r5.2xlarge";
+pub const DEMO_DOMAIN: &str = "demo.encodedcc.org";
+pub const DEFAULT_AWS_REGIONS: &str = "us-west-2";
+pub const ROLE_SESSION_NAME: &str = "rustybot";
+pub const DEFAULT_PROTECTED_TAGS: &str = "protected";
+pub const AUDIT_FILE: &str = "rustybot_audit.log";
 
 pub const MAX_MESSAGE_SIZE: usize = 3900;
+
+pub const MAX_INDEXER_ERRORS: usize = 5;
+pub const INDEXER_PATH: &str = "/_indexer";
+pub const VISINDEXER_PATH: &str = "/_visindexer";
+pub const REGIONINDEXER_PATH: &str = "/_regionindexer";
+pub const DEFAULT_INDEXERS: &str = "indexer,visindexer";
+pub const DEFAULT_POLL_INTERVAL: i64 = 5;
+pub const DEFAULT_SETTLE_POLLS: usize = 13;
+pub const DEFAULT_COMPLETION: &str = "status == waiting";
+pub const VISINDEXER_INITIAL_DELAY: i64 = 60;
+pub const REGIONINDEXER_INITIAL_DELAY: i64 = 60;
+pub const DEFAULT_MAX_FAILURES: usize = 3;
+pub const DEFAULT_HTTP_TIMEOUT: u64 = 10;
+pub const DEFAULT_HTTP_RETRIES: u32 = 2;
+pub const HTTP_BACKOFF: u64 = 1;
+pub const MAX_HTTP_BACKOFF: u64 = 30;
+pub const WAITUP_INTERVAL: i64 = 10;
+pub const DEFAULT_MAX_WAIT: i64 = 900;
+pub const HISTORY_FILE: &str = "rustybot_history.json";
+pub const HISTORY_SIZE: usize = 1000;
+pub const HISTORY_SHOWN: usize = 10;
+pub const TREND_BASELINE_SIZE: usize = 10;
+pub const REGRESSION_THRESHOLD: f64 = 0.2;
+pub const PROGRESS_WINDOW: usize = 12;
+pub const PROGRESS_UPDATE_INTERVAL: i64 = 300;
+
+pub const SCHEDULE_FILE: &str = "rustybot_schedule.json";
+pub const MAX_DELAY_DAYS: i64 = 30;
+pub const SCHEDULE_GRACE_PERIOD: i64 = 300;
@@ -0,0 +1,303 @@
+use crate::bot::{self, Indexer};
+use crate::constants;
+use crate::progress::format_duration;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CycleRecord {
+    pub url: String,
+    pub indexer: String,
+    pub cycle_started: Option<String>,
+    pub took_seconds: f64,
+    pub indexed: Option<u64>,
+    pub recorded_at: DateTime<Utc>
+}
+
+
+impl CycleRecord {
+    // Cycles without a start time are told apart by how long they took.
+    fn is_same_cycle(&self, other: &CycleRecord) -> bool {
+	self.url == other.url
+	    && self.indexer == other.indexer
+	    && self.cycle_started == other.cycle_started
+	    && (self.cycle_started.is_some() || self.took() == other.took())
+    }
+
+    pub fn took(&self) -> Duration {
+	Duration::milliseconds((self.took_seconds * 1000.0) as i64)
+    }
+
+    pub fn describe(&self) -> String {
+	let mut parts = vec![format!("took {}", format_duration(self.took()))];
+	if let Some(started) = &self.cycle_started {
+	    parts.insert(0, format!("started {}", started));
+	}
+	if let Some(indexed) = self.indexed {
+	    parts.push(format!("indexed {}", indexed));
+	}
+	format!("{} {}", self.indexer, parts.join(", "))
+    }
+}
+
+
+// Turns the finished cycles in an indexer response into records.
+pub fn records_from_indexer(url: &str, indexer: &str, response: &Indexer, now: DateTime<Utc>) -> Vec<CycleRecord> {
+    response.results.iter()
+	.filter_map(|result| {
+	    let took = bot::parse_cycle_took(result.cycle_took.as_ref()?)?;
+	    Some(CycleRecord {
+		url: url.to_owned(),
+		indexer: indexer.to_owned(),
+		cycle_started: result.cycle_started.clone(),
+		took_seconds: took.num_milliseconds() as f64 / 1000.0,
+		indexed: result.indexed,
+		recorded_at: now
+	    })
+	})
+	.collect()
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeploymentStats {
+    pub url: String,
+    pub cycles: usize,
+    pub average: Duration,
+    pub longest: Duration,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>
+}
+
+
+// Groups `records` of one indexer by deployment, most recently seen first.
+pub fn deployment_stats(records: &[CycleRecord], indexer: &str) -> Vec<DeploymentStats> {
+    let mut urls: Vec<&str> = vec![];
+    for record in records.iter().rev().filter(|record| record.indexer == indexer) {
+	if !urls.contains(&record.url.as_str()) {
+	    urls.push(&record.url);
+	}
+    }
+    urls.into_iter().map(|url| {
+	let cycles: Vec<&CycleRecord> = records.iter()
+	    .filter(|record| record.indexer == indexer && record.url == url)
+	    .collect();
+	let total: f64 = cycles.iter().map(|record| record.took_seconds).sum();
+	DeploymentStats {
+	    url: url.to_owned(),
+	    cycles: cycles.len(),
+	    average: Duration::milliseconds((total / cycles.len() as f64 * 1000.0) as i64),
+	    longest: cycles.iter().map(|record| record.took()).max().unwrap_or_else(Duration::zero),
+	    first_seen: cycles.iter().map(|record| record.recorded_at).min().unwrap_or_else(Utc::now),
+	    last_seen: cycles.iter().map(|record| record.recorded_at).max().unwrap_or_else(Utc::now)
+	}
+    }).collect()
+}
+
+
+// Compares the longest cycle of `stats`, normally the initial full index of
+// a demo, with the mean of the same figure over the `others` first seen
+// before it. Returns the relative difference, positive when `stats` is
+// slower, and how many deployments it was compared with.
+pub fn compare_to_baseline(stats: &DeploymentStats, others: &[DeploymentStats]) -> Option<(f64, usize)> {
+    let baseline: Vec<f64> = others.iter()
+	.filter(|other| other.url != stats.url && other.first_seen < stats.first_seen)
+	.take(constants::TREND_BASELINE_SIZE)
+	.map(|other| other.longest.num_milliseconds() as f64)
+	.collect();
+    if baseline.is_empty() {
+	return None;
+    }
+    let mean = baseline.iter().sum::<f64>() / baseline.len() as f64;
+    if mean <= 0.0 {
+	return None;
+    }
+    Some(((stats.longest.num_milliseconds() as f64 - mean) / mean, baseline.len()))
+}
+
+
+pub fn describe_comparison(difference: f64, others: usize) -> String {
+    let percent = (difference * 100.0).abs().round();
+    let direction = if difference >= 0.0 { "slower" } else { "faster" };
+    let mut value = format!("{}% {} than the last {} deployments", percent, direction, others);
+    if difference >= constants::REGRESSION_THRESHOLD {
+	value.push_str(" (REGRESSION)");
+    }
+    value
+}
+
+
+pub struct History {
+    path: PathBuf,
+    lock: Mutex<()>
+}
+
+
+impl History {
+    pub fn new(path: PathBuf) -> Self {
+	History {
+	    path,
+	    lock: Mutex::new(())
+	}
+    }
+
+    pub fn from_env() -> Self {
+	History::new(
+	    env::var("RUSTY_BOT_HISTORY")
+		.unwrap_or_else(|_| constants::HISTORY_FILE.to_owned())
+		.into()
+	)
+    }
+
+    fn read(&self) -> Vec<CycleRecord> {
+	fs::read_to_string(&self.path)
+	    .ok()
+	    .and_then(|contents| serde_json::from_str(&contents).ok())
+	    .unwrap_or_default()
+    }
+
+    fn write(&self, records: &[CycleRecord]) {
+	match serde_json::to_string(records) {
+	    Ok(contents) => {
+		if let Err(error) = fs::write(&self.path, contents) {
+		    println!("Error writing history {:?}: {}", &self.path, error);
+		}
+	    },
+	    Err(error) => println!("Error serializing history: {}", error),
+	}
+    }
+
+    pub fn load(&self) -> Vec<CycleRecord> {
+	let _guard = self.lock.lock().unwrap();
+	self.read()
+    }
+
+    // Adds cycles not recorded yet, keeping the newest HISTORY_SIZE records.
+    pub fn record(&self, new_records: Vec<CycleRecord>) {
+	let _guard = self.lock.lock().unwrap();
+	let mut records = self.read();
+	let total = records.len();
+	for record in new_records {
+	    if !records.iter().any(|existing| existing.is_same_cycle(&record)) {
+		records.push(record);
+	    }
+	}
+	if records.len() == total {
+	    return;
+	}
+	let overflow = records.len().saturating_sub(constants::HISTORY_SIZE);
+	records.drain(..overflow);
+	self.write(&records);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+
+    fn make_record(url: &str, started: &str, took_seconds: f64, minute: u32) -> CycleRecord {
+	CycleRecord {
+	    url: url.to_owned(),
+	    indexer: "indexer".to_owned(),
+	    cycle_started: Some(started.to_owned()),
+	    took_seconds,
+	    indexed: None,
+	    recorded_at: Utc.with_ymd_and_hms(2020, 6, 1, 12, minute, 0).unwrap()
+	}
+    }
+
+
+    #[test]
+    fn test_records_from_indexer() {
+	let response: Indexer = serde_json::from_str(r#"{
+	    "status": "waiting",
+	    "results": [
+		{"cycle_started": "2020-06-01T11:00:00", "cycle_took": "0:01:30.5", "indexed": 500},
+		{"cycle_started": "2020-06-01T10:00:00"}
+	    ]
+	}"#).unwrap();
+	let now = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+	let records = records_from_indexer("https://a.org", "indexer", &response, now);
+	assert_eq!(records.len(), 1);
+	assert_eq!(records[0].took_seconds, 90.5);
+	assert_eq!(records[0].indexed, Some(500));
+	assert_eq!(records[0].describe(), "indexer started 2020-06-01T11:00:00, took 1m 30s, indexed 500");
+    }
+
+
+    #[test]
+    fn test_deployment_stats() {
+	let records = vec![
+	    make_record("https://a.org", "1", 100.0, 0),
+	    make_record("https://b.org", "1", 50.0, 1),
+	    make_record("https://a.org", "2", 300.0, 2),
+	];
+	let stats = deployment_stats(&records, "indexer");
+	assert_eq!(stats.len(), 2);
+	assert_eq!(stats[0].url, "https://a.org");
+	assert_eq!(stats[0].cycles, 2);
+	assert_eq!(stats[0].average, Duration::seconds(200));
+	assert_eq!(stats[0].longest, Duration::seconds(300));
+	assert_eq!(stats[1].url, "https://b.org");
+	assert!(deployment_stats(&records, "visindexer").is_empty());
+    }
+
+
+    #[test]
+    fn test_compare_to_baseline() {
+	let records = vec![
+	    make_record("https://a.org", "1", 100.0, 0),
+	    make_record("https://b.org", "1", 100.0, 1),
+	    make_record("https://c.org", "1", 140.0, 2),
+	];
+	let stats = deployment_stats(&records, "indexer");
+	let (difference, others) = compare_to_baseline(&stats[0], &stats).unwrap();
+	assert!((difference - 0.4).abs() < 1e-9);
+	assert_eq!(describe_comparison(difference, others), "40% slower than the last 2 deployments (REGRESSION)");
+	assert_eq!(compare_to_baseline(&stats[0], &stats[..1]), None);
+    }
+
+
+    #[test]
+    fn test_compare_to_baseline_ignores_later_deployments() {
+	let records = vec![
+	    make_record("https://a.org", "1", 100.0, 0),
+	    make_record("https://b.org", "1", 140.0, 1),
+	    make_record("https://c.org", "1", 70.0, 2),
+	    make_record("https://d.org", "1", 70.0, 3),
+	    make_record("https://b.org", "2", 10.0, 4),
+	];
+	let stats = deployment_stats(&records, "indexer");
+	assert_eq!(stats[0].url, "https://b.org");
+	let (difference, others) = compare_to_baseline(&stats[0], &stats).unwrap();
+	assert_eq!(others, 1);
+	assert_eq!(describe_comparison(difference, others), "40% slower than the last 1 deployments (REGRESSION)");
+	let a = stats.iter().find(|stats| stats.url == "https://a.org").unwrap();
+	assert_eq!(compare_to_baseline(a, &stats), None);
+    }
+
+
+    #[test]
+    fn test_history_record_dedupes_and_persists() {
+	let path = env::temp_dir().join(format!("rustybot_history_test_{}.json", std::process::id()));
+	let history = History::new(path.clone());
+	history.record(vec![make_record("https://a.org", "1", 100.0, 0)]);
+	history.record(vec![make_record("https://a.org", "1", 100.0, 1), make_record("https://a.org", "2", 90.0, 1)]);
+	let records = History::new(path.clone()).load();
+	assert_eq!(records.len(), 2);
+	assert_eq!(records[1].cycle_started, Some("2".to_owned()));
+	let unstarted = |took_seconds, minute| CycleRecord { cycle_started: None, ..make_record("https://a.org", "", took_seconds, minute) };
+	history.record(vec![unstarted(80.0, 2)]);
+	history.record(vec![unstarted(80.0, 3), unstarted(70.0, 3)]);
+	assert_eq!(history.load().len(), 4);
+	let _ = fs::remove_file(path);
+    }
+}
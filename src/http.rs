@@ -0,0 +1,231 @@
+use crate::constants;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::env;
+use std::time::Duration;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    Basic {
+	user: String,
+	password: Option<String>
+    },
+    Bearer(String)
+}
+
+
+impl Credentials {
+    // RUSTY_BOT_HTTP_TOKEN takes precedence over RUSTY_BOT_HTTP_USER and
+    // RUSTY_BOT_HTTP_PASSWORD. Credentials never come from Slack messages.
+    pub fn from_env() -> Option<Self> {
+	if let Ok(token) = env::var("RUSTY_BOT_HTTP_TOKEN") {
+	    return Some(Credentials::Bearer(token));
+	}
+	let user = env::var("RUSTY_BOT_HTTP_USER").ok()?;
+	Some(Credentials::Basic {
+	    user,
+	    password: env::var("RUSTY_BOT_HTTP_PASSWORD").ok()
+	})
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+    pub credentials: Option<Credentials>,
+    // Hosts `credentials` may be sent to, as `host` or `.domain` for every
+    // host under it.
+    pub credential_hosts: Vec<String>
+}
+
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+	HttpOptions {
+	    timeout: Duration::from_secs(constants::DEFAULT_HTTP_TIMEOUT),
+	    retries: constants::DEFAULT_HTTP_RETRIES,
+	    backoff: Duration::from_secs(constants::HTTP_BACKOFF),
+	    credentials: None,
+	    credential_hosts: vec![]
+	}
+    }
+}
+
+
+impl HttpOptions {
+    pub fn from_env() -> Self {
+	HttpOptions {
+	    credentials: Credentials::from_env(),
+	    credential_hosts: parse_hosts(&env::var("RUSTY_BOT_HTTP_HOSTS").unwrap_or_default()),
+	    ..Default::default()
+	}
+    }
+}
+
+
+// Doubles `base` for every failed attempt, capped at MAX_HTTP_BACKOFF.
+pub fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let max = Duration::from_secs(constants::MAX_HTTP_BACKOFF);
+    base.checked_mul(2u32.saturating_pow(attempt))
+	.map(|delay| delay.min(max))
+	.unwrap_or(max)
+}
+
+
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+
+fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+	|| error.is_connect()
+	|| error.status().map(is_transient_status).unwrap_or(false)
+}
+
+
+pub fn endpoint(url: &str, path: &str) -> String {
+    format!("{}/{}", url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+
+pub fn parse_hosts(value: &str) -> Vec<String> {
+    value.split(',')
+	.map(|host| host.trim().to_lowercase())
+	.filter(|host| !host.is_empty())
+	.collect()
+}
+
+
+// Returns the credentials to send to `url`, only over https and only to
+// hosts in `options.credential_hosts`, since URLs come from Slack messages.
+fn credentials_for<'a>(url: &str, options: &'a HttpOptions) -> Option<&'a Credentials> {
+    let url = reqwest::Url::parse(url).ok()?;
+    if url.scheme() != "https" {
+	return None;
+    }
+    let host = url.host_str()?.to_lowercase();
+    let allowed = options.credential_hosts.iter().any(|allowed| match allowed.strip_prefix('.') {
+	Some(domain) => host == domain || host.ends_with(allowed.as_str()),
+	None => host == *allowed,
+    });
+    if !allowed {
+	return None;
+    }
+    options.credentials.as_ref()
+}
+
+
+fn authorize(request: reqwest::RequestBuilder, url: &str, options: &HttpOptions) -> reqwest::RequestBuilder {
+    match credentials_for(url, options) {
+	Some(Credentials::Basic { user, password }) => request.basic_auth(user, password.as_ref()),
+	Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+	None => request,
+    }
+}
+
+
+fn make_client(options: &HttpOptions) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+	.timeout(options.timeout)
+	.build()
+}
+
+
+// Fetches `path` on `url` as JSON, retrying timeouts, connection errors and
+// 5xx or 429 responses up to `options.retries` times.
+#[tokio::main]
+pub async fn get_json(url: &str, path: &str, options: &HttpOptions) -> Result<Value, reqwest::Error> {
+    let endpoint = endpoint(url, path);
+    let client = make_client(options)?;
+    let mut attempt = 0;
+    loop {
+	let request = authorize(client.get(&endpoint), &endpoint, options);
+	let result: Result<Value, reqwest::Error> = async {
+	    request.send()
+		.await?
+		.error_for_status()?
+		.json()
+		.await
+	}.await;
+	match result {
+	    Err(error) if attempt < options.retries && is_transient(&error) => {
+		println!("Retrying {} after error: {}", &endpoint, error);
+		tokio::time::delay_for(backoff_delay(options.backoff, attempt)).await;
+		attempt += 1;
+	    },
+	    result => return result,
+	}
+    }
+}
+
+
+// Returns the status of a single GET without retries, for health checks.
+#[tokio::main]
+pub async fn get_status(url: &str, options: &HttpOptions) -> Result<StatusCode, reqwest::Error> {
+    let client = make_client(options)?;
+    let response = authorize(client.get(url), url, options)
+	.send()
+	.await?;
+    Ok(response.status())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_backoff_delay() {
+	let base = Duration::from_secs(1);
+	assert_eq!(backoff_delay(base, 0), Duration::from_secs(1));
+	assert_eq!(backoff_delay(base, 3), Duration::from_secs(8));
+	assert_eq!(backoff_delay(base, 10), Duration::from_secs(constants::MAX_HTTP_BACKOFF));
+	assert_eq!(backoff_delay(base, 40), Duration::from_secs(constants::MAX_HTTP_BACKOFF));
+    }
+
+
+    #[test]
+    fn test_is_transient_status() {
+	assert!(is_transient_status(StatusCode::BAD_GATEWAY));
+	assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+	assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+	assert!(!is_transient_status(StatusCode::NOT_FOUND));
+	assert!(!is_transient_status(StatusCode::UNAUTHORIZED));
+    }
+
+
+    #[test]
+    fn test_credentials_only_go_to_configured_https_hosts() {
+	let options = HttpOptions {
+	    credentials: Some(Credentials::Bearer("secret".to_owned())),
+	    credential_hosts: parse_hosts("www.encodeproject.org, .Demo.EncodedCC.org,"),
+	    ..Default::default()
+	};
+	let token = options.credentials.as_ref();
+	assert_eq!(options.credential_hosts, vec!["www.encodeproject.org", ".demo.encodedcc.org"]);
+	assert_eq!(credentials_for("https://www.encodeproject.org/_indexer", &options), token);
+	assert_eq!(credentials_for("https://encd-5328-emma.demo.encodedcc.org/", &options), token);
+	assert_eq!(credentials_for("https://demo.encodedcc.org/", &options), token);
+	assert_eq!(credentials_for("http://www.encodeproject.org/_indexer", &options), None);
+	assert_eq!(credentials_for("https://attacker.example/", &options), None);
+	assert_eq!(credentials_for("https://evildemo.encodedcc.org/", &options), None);
+	assert_eq!(credentials_for("https://www.encodeproject.org.attacker.example/", &options), None);
+	assert_eq!(credentials_for("https://www.encodeproject.org@attacker.example/", &options), None);
+	assert_eq!(credentials_for("not a url", &options), None);
+	let options = HttpOptions { credential_hosts: vec![], ..options };
+	assert_eq!(credentials_for("https://www.encodeproject.org/_indexer", &options), None);
+    }
+
+
+    #[test]
+    fn test_endpoint() {
+	assert_eq!(endpoint("https://test.encodedcc.org", "/_indexer"), "https://test.encodedcc.org/_indexer");
+	assert_eq!(endpoint("https://test.encodedcc.org/", "_health"), "https://test.encodedcc.org/_health");
+    }
+}
@@ -1,7 +1,17 @@
+mod audit;
 mod aws;
 mod bot;
+mod commands;
 mod connection;
 mod constants;
+mod history;
+mod http;
+mod monitor;
+mod predicate;
+mod progress;
+mod registry;
+mod schedule;
+mod tokenize;
 
 use std::env;
 use connection::Connection;
@@ -9,11 +19,8 @@ use connection::Connection;
 #[macro_use]
 extern crate lazy_static;
 
-#[macro_use]
-extern crate log;
-
 fn main() {
     env_logger::init();
     let token = env::var("RUSTY_BOT_TOKEN").unwrap();
-    Connection::new(&token).listen();
+    let _ = Connection::new(&token).listen();
 }
@@ -0,0 +1,337 @@
+use crate::bot::{self, Indexer};
+use crate::constants;
+use crate::http::HttpOptions;
+use crate::predicate::Predicate;
+use crate::schedule;
+use chrono::Duration;
+use clap::ArgMatches;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::str::FromStr;
+
+
+pub const EXPECTED_STATUSES: [&str; 2] = ["indexing", "waiting"];
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    Continue,
+    Abort
+}
+
+
+impl FromStr for OnError {
+    type Err = Box<dyn Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+	match value {
+	    "continue" => Ok(OnError::Continue),
+	    "abort" => Ok(OnError::Abort),
+	    _ => Err(format!("Invalid --on-error `{}`, expected continue or abort", value).into()),
+	}
+    }
+}
+
+
+// An indexer endpoint that konitor and kronitor can wait on. `label` prefixes
+// the START and DONE messages and secondary indexers get a head start since
+// they only pick up work once the primary indexer hands it over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexerEndpoint {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub path: &'static str,
+    pub initial_delay: i64
+}
+
+
+pub const INDEXER: IndexerEndpoint = IndexerEndpoint {
+    name: "indexer",
+    label: "",
+    path: constants::INDEXER_PATH,
+    initial_delay: 0
+};
+
+
+pub const VISINDEXER: IndexerEndpoint = IndexerEndpoint {
+    name: "visindexer",
+    label: "vis_indexer ",
+    path: constants::VISINDEXER_PATH,
+    initial_delay: constants::VISINDEXER_INITIAL_DELAY
+};
+
+
+pub const REGIONINDEXER: IndexerEndpoint = IndexerEndpoint {
+    name: "regionindexer",
+    label: "region_indexer ",
+    path: constants::REGIONINDEXER_PATH,
+    initial_delay: constants::REGIONINDEXER_INITIAL_DELAY
+};
+
+
+pub const INDEXERS: [IndexerEndpoint; 3] = [INDEXER, VISINDEXER, REGIONINDEXER];
+
+
+// Parses `indexer,visindexer,regionindexer`, also accepting `vis_indexer` or
+// `_regionindexer` spellings.
+pub fn parse_indexers(value: &str) -> Result<Vec<IndexerEndpoint>, Box<dyn Error>> {
+    let mut indexers = vec![];
+    for name in value.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+	let normalized = name.trim_start_matches('_').replace('_', "");
+	let indexer = INDEXERS.iter()
+	    .find(|indexer| indexer.name == normalized)
+	    .ok_or_else(|| format!(
+		"Unknown indexer `{}`, expected {}",
+		name,
+		INDEXERS.iter().map(|indexer| indexer.name).collect::<Vec<_>>().join(", ")
+	    ))?;
+	if !indexers.contains(indexer) {
+	    indexers.push(*indexer);
+	}
+    }
+    if indexers.is_empty() {
+	return Err("No indexers given".into());
+    }
+    Ok(indexers)
+}
+
+
+// Accepts `90` as seconds as well as the `1m30s` forms used for scheduling,
+// up to the longest delay the scheduler accepts.
+pub fn parse_seconds(value: &str) -> Option<Duration> {
+    let duration = match value.parse::<i64>() {
+	Ok(seconds) if seconds >= 0 => Duration::try_seconds(seconds),
+	Ok(_) => None,
+	Err(_) => schedule::parse_duration(value),
+    };
+    duration.filter(|duration| *duration <= Duration::days(constants::MAX_DELAY_DAYS))
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorOptions {
+    pub on_error: OnError,
+    pub interval: Duration,
+    pub settle: usize,
+    // None leaves the delay to the indexer's own `initial_delay`.
+    pub initial_delay: Option<Duration>,
+    pub until: Predicate,
+    pub indexers: Vec<IndexerEndpoint>,
+    pub parallel: bool,
+    // Consecutive failed polls, each after its own retries, before aborting.
+    pub max_failures: usize,
+    pub http: HttpOptions
+}
+
+
+impl Default for MonitorOptions {
+    fn default() -> Self {
+	MonitorOptions {
+	    on_error: OnError::Continue,
+	    interval: Duration::seconds(constants::DEFAULT_POLL_INTERVAL),
+	    settle: constants::DEFAULT_SETTLE_POLLS,
+	    initial_delay: None,
+	    until: constants::DEFAULT_COMPLETION.parse().unwrap(),
+	    indexers: parse_indexers(constants::DEFAULT_INDEXERS).unwrap(),
+	    parallel: false,
+	    max_failures: constants::DEFAULT_MAX_FAILURES,
+	    http: HttpOptions::from_env()
+	}
+    }
+}
+
+
+impl MonitorOptions {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Box<dyn Error>> {
+	let mut options = MonitorOptions::default();
+	if let Some(on_error) = matches.value_of("on_error") {
+	    options.on_error = on_error.parse()?;
+	}
+	if let Some(interval) = matches.value_of("interval") {
+	    options.interval = parse_seconds(interval)
+		.filter(|interval| *interval > Duration::zero())
+		.ok_or_else(|| format!("Invalid interval `{}`, expected e.g. 30s or 2m", interval))?;
+	}
+	if let Some(settle) = matches.value_of("settle") {
+	    options.settle = settle.parse::<usize>()
+		.ok()
+		.filter(|settle| *settle > 0)
+		.ok_or_else(|| format!("Invalid settle `{}`, expected a number of polls", settle))?;
+	}
+	if let Some(delay) = matches.value_of("initial_delay") {
+	    options.initial_delay = Some(
+		parse_seconds(delay)
+		    .ok_or_else(|| format!("Invalid initial delay `{}`, expected e.g. 60s or 2m", delay))?
+	    );
+	}
+	if let Some(until) = matches.value_of("until") {
+	    options.until = until.parse()?;
+	}
+	if let Some(indexers) = matches.value_of("indexers") {
+	    options.indexers = parse_indexers(indexers)?;
+	} else if let Ok(indexers) = env::var("RUSTY_BOT_INDEXERS") {
+	    options.indexers = parse_indexers(&indexers)
+		.map_err(|error| format!("Invalid RUSTY_BOT_INDEXERS: {}", error))?;
+	}
+	options.parallel = matches.is_present("parallel");
+	if let Some(max_failures) = matches.value_of("max_failures") {
+	    options.max_failures = max_failures.parse::<usize>()
+		.ok()
+		.filter(|max_failures| *max_failures > 0)
+		.ok_or_else(|| format!("Invalid max failures `{}`, expected a number of polls", max_failures))?;
+	}
+	if let Some(timeout) = matches.value_of("timeout") {
+	    options.http.timeout = parse_seconds(timeout)
+		.and_then(|timeout| timeout.to_std().ok())
+		.filter(|timeout| timeout.as_millis() > 0)
+		.ok_or_else(|| format!("Invalid timeout `{}`, expected e.g. 10s", timeout))?;
+	}
+	if let Some(retries) = matches.value_of("retries") {
+	    options.http.retries = retries.parse::<u32>()
+		.map_err(|_| format!("Invalid retries `{}`, expected a number", retries))?;
+	}
+	Ok(options)
+    }
+}
+
+
+// Remembers which indexer errors and statuses were already reported so each
+// problem is posted once, when it first appears.
+#[derive(Debug, Default)]
+pub struct ProblemTracker {
+    seen_errors: HashSet<String>,
+    last_status: Option<String>
+}
+
+
+impl ProblemTracker {
+    pub fn new_problems(&mut self, indexer: &Indexer) -> Vec<String> {
+	let mut problems = vec![];
+	if !EXPECTED_STATUSES.contains(&indexer.status.as_str())
+	    && self.last_status.as_ref() != Some(&indexer.status) {
+	    problems.push(format!("Unexpected indexer status `{}`", indexer.status));
+	}
+	self.last_status = Some(indexer.status.clone());
+	for error in indexer.errors() {
+	    let description = bot::describe_indexer_error(error);
+	    if self.seen_errors.insert(description.clone()) {
+		problems.push(format!("Indexing error {}", description));
+	    }
+	}
+	problems
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::make_registry;
+
+
+    fn make_indexer(body: &str) -> Indexer {
+	serde_json::from_str(body).unwrap()
+    }
+
+
+    #[test]
+    fn test_on_error_from_str() {
+	assert_eq!("abort".parse::<OnError>().unwrap(), OnError::Abort);
+	assert_eq!("continue".parse::<OnError>().unwrap(), OnError::Continue);
+	assert!("explode".parse::<OnError>().is_err());
+    }
+
+
+    #[test]
+    fn test_parse_indexers() {
+	assert_eq!(parse_indexers("indexer,visindexer").unwrap(), vec![INDEXER, VISINDEXER]);
+	assert_eq!(
+	    parse_indexers("_regionindexer, vis_indexer,regionindexer").unwrap(),
+	    vec![REGIONINDEXER, VISINDEXER]
+	);
+	assert_eq!(
+	    parse_indexers("indexer,search").unwrap_err().to_string(),
+	    "Unknown indexer `search`, expected indexer, visindexer, regionindexer"
+	);
+	assert!(parse_indexers(" , ").is_err());
+    }
+
+
+    #[test]
+    fn test_parse_seconds() {
+	assert_eq!(parse_seconds("90"), Some(Duration::seconds(90)));
+	assert_eq!(parse_seconds("2m"), Some(Duration::minutes(2)));
+	assert_eq!(parse_seconds("-5"), None);
+	assert_eq!(parse_seconds("soon"), None);
+	assert_eq!(parse_seconds("30d"), Some(Duration::days(30)));
+	assert_eq!(parse_seconds("31d"), None);
+	assert_eq!(parse_seconds("9223372036854775807"), None);
+	assert_eq!(parse_seconds("9999999999999d"), None);
+    }
+
+
+    #[test]
+    fn test_monitor_options_from_matches() {
+	let matches = make_registry().make_app().get_matches_from_safe(vec![
+	    "<@rustybot>", "konitor", "https://test.encodedcc.org/", "--interval", "30s", "--settle", "4",
+	    "--initial-delay", "0", "--until", "status == waiting && results[0].invalidated == 0", "--on-error", "abort",
+	    "--indexers", "indexer,regionindexer", "--parallel", "--timeout", "30s", "--retries", "0", "--max-failures", "5"
+	]).unwrap();
+	let options = MonitorOptions::from_matches(matches.subcommand_matches("konitor").unwrap()).unwrap();
+	assert_eq!(options.interval, Duration::seconds(30));
+	assert_eq!(options.settle, 4);
+	assert_eq!(options.initial_delay, Some(Duration::zero()));
+	assert_eq!(options.on_error, OnError::Abort);
+	assert_eq!(options.indexers, vec![INDEXER, REGIONINDEXER]);
+	assert!(options.parallel);
+	assert_eq!(options.http.timeout, std::time::Duration::from_secs(30));
+	assert_eq!(options.http.retries, 0);
+	assert_eq!(options.max_failures, 5);
+	assert!(options.until.evaluate(&serde_json::json!({"status": "waiting", "results": [{"invalidated": 0}]})));
+	let matches = make_registry().make_app().get_matches_from_safe(vec![
+	    "<@rustybot>", "monitor", "https://test.encodedcc.org/", "--settle", "0"
+	]).unwrap();
+	assert!(MonitorOptions::from_matches(matches.subcommand_matches("monitor").unwrap()).is_err());
+    }
+
+
+    #[test]
+    fn test_monitor_accepts_several_urls() {
+	let matches = make_registry().make_app().get_matches_from_safe(vec![
+	    "<@rustybot>", "monitor", "https://test.encodedcc.org/", "https://www.encodeproject.org/", "--settle", "2"
+	]).unwrap();
+	let monitor = matches.subcommand_matches("monitor").unwrap();
+	assert_eq!(monitor.values_of("url").unwrap().count(), 2);
+	assert_eq!(MonitorOptions::from_matches(monitor).unwrap().settle, 2);
+    }
+
+
+    #[test]
+    fn test_default_completion() {
+	let options = MonitorOptions::default();
+	assert!(options.until.evaluate(&serde_json::json!({"status": "waiting"})));
+	assert!(!options.until.evaluate(&serde_json::json!({"status": "indexing"})));
+    }
+
+
+    #[test]
+    fn test_problem_tracker_reports_new_problems_once() {
+	let mut tracker = ProblemTracker::default();
+	let indexer = make_indexer(r#"{"status": "indexing"}"#);
+	assert_eq!(tracker.new_problems(&indexer), Vec::<String>::new());
+	let indexer = make_indexer(
+	    r#"{"status": "indexing", "state": {"errors": [{"uuid": "abc", "error_message": "Timeout"}]}}"#
+	);
+	assert_eq!(tracker.new_problems(&indexer), vec!["Indexing error abc: Timeout"]);
+	assert_eq!(tracker.new_problems(&indexer), Vec::<String>::new());
+	let indexer = make_indexer(
+	    r#"{"status": "error", "state": {"errors": ["abc: Timeout", "def: Conflict"]}}"#
+	);
+	assert_eq!(
+	    tracker.new_problems(&indexer),
+	    vec!["Unexpected indexer status `error`", "Indexing error def: Conflict"]
+	);
+	assert_eq!(tracker.new_problems(&indexer), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::tokenize::closing_quote;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+
+#[derive(Debug, PartialEq)]
+pub enum PredicateError {
+    Empty,
+    UnterminatedString(char),
+    InvalidPath(String),
+    Unexpected(String),
+    MissingValue(String)
+}
+
+
+impl fmt::Display for PredicateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    PredicateError::Empty => write!(f, "Empty condition"),
+	    PredicateError::UnterminatedString(quote) => write!(f, "Missing closing {} in condition", quote),
+	    PredicateError::InvalidPath(path) => write!(f, "Invalid path `{}` in condition", path),
+	    PredicateError::Unexpected(token) => write!(f, "Unexpected `{}` in condition", token),
+	    PredicateError::MissingValue(op) => write!(f, "Missing value after `{}` in condition", op),
+	}
+    }
+}
+
+
+impl Error for PredicateError {}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathPart {
+    Key(String),
+    Index(usize)
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	let symbol = match self {
+	    Op::Eq => "==",
+	    Op::Ne => "!=",
+	    Op::Lt => "<",
+	    Op::Le => "<=",
+	    Op::Gt => ">",
+	    Op::Ge => ">=",
+	};
+	write!(f, "{}", symbol)
+    }
+}
+
+
+// A condition over a JSON document such as
+// `status == "waiting" && results[0].invalidated == 0`. `&&` binds tighter
+// than `||`, a bare path is true when its value is truthy, and paths that do
+// not resolve compare as null.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Truthy(Vec<PathPart>),
+    Compare(Vec<PathPart>, Op, Value),
+    All(Vec<Predicate>),
+    Any(Vec<Predicate>)
+}
+
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Op(Op),
+    And,
+    Or
+}
+
+
+fn lex(text: &str) -> Result<Vec<Token>, PredicateError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+	if c.is_whitespace() {
+	    chars.next();
+	} else if let Some(closing) = closing_quote(c) {
+	    chars.next();
+	    let mut string = String::new();
+	    loop {
+		match chars.next() {
+		    Some(c) if c == closing => break,
+		    Some(c) => string.push(c),
+		    None => return Err(PredicateError::UnterminatedString(closing)),
+		}
+	    }
+	    tokens.push(Token::Text(string));
+	} else if "=!<>&|".contains(c) {
+	    let mut symbol = String::new();
+	    while let Some(&c) = chars.peek() {
+		if !"=!<>&|".contains(c) {
+		    break;
+		}
+		symbol.push(c);
+		chars.next();
+	    }
+	    tokens.push(match symbol.as_str() {
+		"==" | "=" => Token::Op(Op::Eq),
+		"!=" => Token::Op(Op::Ne),
+		"<" => Token::Op(Op::Lt),
+		"<=" => Token::Op(Op::Le),
+		">" => Token::Op(Op::Gt),
+		">=" => Token::Op(Op::Ge),
+		"&&" => Token::And,
+		"||" => Token::Or,
+		_ => return Err(PredicateError::Unexpected(symbol)),
+	    });
+	} else {
+	    let mut word = String::new();
+	    while let Some(&c) = chars.peek() {
+		if c.is_whitespace() || "=!<>&|".contains(c) || closing_quote(c).is_some() {
+		    break;
+		}
+		word.push(c);
+		chars.next();
+	    }
+	    tokens.push(match word.as_str() {
+		"and" => Token::And,
+		"or" => Token::Or,
+		_ => Token::Word(word),
+	    });
+	}
+    }
+    Ok(tokens)
+}
+
+
+// Parses `results[0].invalidated`, optionally prefixed with `$.` or `.`.
+pub fn parse_path(path: &str) -> Result<Vec<PathPart>, PredicateError> {
+    let invalid = || PredicateError::InvalidPath(path.to_owned());
+    let trimmed = path.trim_start_matches('$').trim_start_matches('.');
+    let mut parts = vec![];
+    if trimmed.is_empty() {
+	return Ok(parts);
+    }
+    for segment in trimmed.split('.') {
+	let (key, mut indexes) = match segment.find('[') {
+	    Some(start) => (&segment[..start], &segment[start..]),
+	    None => (segment, ""),
+	};
+	if !key.is_empty() {
+	    parts.push(PathPart::Key(key.to_owned()));
+	} else if indexes.is_empty() {
+	    return Err(invalid());
+	}
+	while !indexes.is_empty() {
+	    let end = indexes.find(']').ok_or_else(invalid)?;
+	    if !indexes.starts_with('[') {
+		return Err(invalid());
+	    }
+	    let index = indexes[1..end].parse::<usize>().map_err(|_| invalid())?;
+	    parts.push(PathPart::Index(index));
+	    indexes = &indexes[end + 1..];
+	}
+    }
+    Ok(parts)
+}
+
+
+pub fn lookup<'v>(value: &'v Value, path: &[PathPart]) -> Option<&'v Value> {
+    path.iter().try_fold(value, |value, part| match part {
+	PathPart::Key(key) => value.get(key),
+	PathPart::Index(index) => value.get(index),
+    })
+}
+
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+	Value::Null => false,
+	Value::Bool(value) => *value,
+	Value::Number(value) => value.as_f64() != Some(0.0),
+	Value::String(value) => !value.is_empty(),
+	Value::Array(value) => !value.is_empty(),
+	Value::Object(value) => !value.is_empty(),
+    }
+}
+
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+	(Value::Number(left), Value::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+	(Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+	(Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+	(Value::Null, Value::Null) => Some(Ordering::Equal),
+	_ => None
+    }
+}
+
+
+fn parse_literal(token: Token, op: &str) -> Result<Value, PredicateError> {
+    match token {
+	Token::Text(text) => Ok(Value::String(text)),
+	Token::Word(word) => Ok(serde_json::from_str(&word).unwrap_or(Value::String(word))),
+	_ => Err(PredicateError::MissingValue(op.to_owned())),
+    }
+}
+
+
+fn parse_term(tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>) -> Result<Predicate, PredicateError> {
+    let path = match tokens.next() {
+	Some(Token::Word(word)) => parse_path(&word)?,
+	Some(Token::Text(text)) => return Err(PredicateError::Unexpected(text)),
+	Some(Token::And) => return Err(PredicateError::Unexpected("&&".to_owned())),
+	Some(Token::Or) => return Err(PredicateError::Unexpected("||".to_owned())),
+	Some(Token::Op(op)) => return Err(PredicateError::Unexpected(op.to_string())),
+	None => return Err(PredicateError::Empty),
+    };
+    let op = match tokens.peek() {
+	Some(Token::Op(op)) => *op,
+	_ => return Ok(Predicate::Truthy(path)),
+    };
+    tokens.next();
+    let op_name = op.to_string();
+    let value = tokens.next()
+	.ok_or_else(|| PredicateError::MissingValue(op_name.clone()))
+	.and_then(|token| parse_literal(token, &op_name))?;
+    Ok(Predicate::Compare(path, op, value))
+}
+
+
+impl FromStr for Predicate {
+    type Err = PredicateError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+	let mut tokens = lex(text)?.into_iter().peekable();
+	let mut any = vec![];
+	let mut all = vec![parse_term(&mut tokens)?];
+	while let Some(token) = tokens.next() {
+	    match token {
+		Token::And => all.push(parse_term(&mut tokens)?),
+		Token::Or => {
+		    any.push(Predicate::all(std::mem::take(&mut all)));
+		    all.push(parse_term(&mut tokens)?);
+		},
+		Token::Word(word) | Token::Text(word) => return Err(PredicateError::Unexpected(word)),
+		Token::Op(op) => return Err(PredicateError::Unexpected(op.to_string())),
+	    }
+	}
+	any.push(Predicate::all(all));
+	Ok(match any.len() {
+	    1 => any.pop().unwrap(),
+	    _ => Predicate::Any(any),
+	})
+    }
+}
+
+
+impl Predicate {
+    fn all(mut predicates: Vec<Predicate>) -> Predicate {
+	match predicates.len() {
+	    1 => predicates.pop().unwrap(),
+	    _ => Predicate::All(predicates),
+	}
+    }
+
+    pub fn evaluate(&self, value: &Value) -> bool {
+	match self {
+	    Predicate::Truthy(path) => lookup(value, path).map(is_truthy).unwrap_or(false),
+	    Predicate::Compare(path, op, expected) => {
+		let actual = lookup(value, path).unwrap_or(&Value::Null);
+		let ordering = compare(actual, expected);
+		match op {
+		    Op::Eq => ordering == Some(Ordering::Equal),
+		    Op::Ne => ordering != Some(Ordering::Equal),
+		    Op::Lt => ordering == Some(Ordering::Less),
+		    Op::Le => matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal)),
+		    Op::Gt => ordering == Some(Ordering::Greater),
+		    Op::Ge => matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal)),
+		}
+	    },
+	    Predicate::All(predicates) => predicates.iter().all(|predicate| predicate.evaluate(value)),
+	    Predicate::Any(predicates) => predicates.iter().any(|predicate| predicate.evaluate(value)),
+	}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+
+    fn make_body() -> Value {
+	json!({
+	    "status": "waiting",
+	    "uuids_in_progress": 0,
+	    "listener": {"status": "indexing"},
+	    "results": [{"invalidated": 0, "indexed": 12.0, "errors": null}, {"invalidated": 3}]
+	})
+    }
+
+
+    fn evaluate(text: &str) -> bool {
+	text.parse::<Predicate>().unwrap().evaluate(&make_body())
+    }
+
+
+    #[test]
+    fn test_parse_path() {
+	assert_eq!(
+	    parse_path("$.results[0].invalidated").unwrap(),
+	    vec![PathPart::Key("results".to_owned()), PathPart::Index(0), PathPart::Key("invalidated".to_owned())]
+	);
+	assert_eq!(parse_path(".status").unwrap(), vec![PathPart::Key("status".to_owned())]);
+	assert_eq!(parse_path("[1]").unwrap(), vec![PathPart::Index(1)]);
+	assert!(parse_path("results[x]").is_err());
+	assert!(parse_path("results..status").is_err());
+    }
+
+
+    #[test]
+    fn test_evaluate_comparisons() {
+	assert!(evaluate(r#"status == "waiting""#));
+	assert!(evaluate("status == waiting"));
+	assert!(evaluate("status == “waiting”"));
+	assert!(!evaluate("status != waiting"));
+	assert!(evaluate("results[0].invalidated == 0"));
+	assert!(evaluate("results[0].indexed >= 12"));
+	assert!(evaluate("results[1].invalidated > 2"));
+	assert!(!evaluate("results[1].invalidated < 2"));
+	assert!(evaluate("results[0].errors == null"));
+	assert!(evaluate("missing == null"));
+	assert!(!evaluate("status > 3"));
+    }
+
+
+    #[test]
+    fn test_evaluate_logic() {
+	assert!(evaluate("status == waiting && results[0].invalidated == 0"));
+	assert!(!evaluate("status == waiting and listener.status == waiting"));
+	assert!(evaluate("status == indexing || listener.status == indexing"));
+	assert!(evaluate("status == indexing && missing || uuids_in_progress == 0"));
+	assert!(evaluate("results"));
+	assert!(!evaluate("uuids_in_progress"));
+	assert!(!evaluate("missing"));
+    }
+
+
+    #[test]
+    fn test_parse_errors() {
+	assert_eq!("".parse::<Predicate>(), Err(PredicateError::Empty));
+	assert_eq!("status ==".parse::<Predicate>(), Err(PredicateError::MissingValue("==".to_owned())));
+	assert_eq!("status == \"waiting".parse::<Predicate>(), Err(PredicateError::UnterminatedString('"')));
+	assert_eq!("status === waiting".parse::<Predicate>(), Err(PredicateError::Unexpected("===".to_owned())));
+	assert_eq!("status waiting".parse::<Predicate>(), Err(PredicateError::Unexpected("waiting".to_owned())));
+	assert_eq!("status == waiting &&".parse::<Predicate>(), Err(PredicateError::Empty));
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+use crate::constants;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub at: DateTime<Utc>,
+    pub queued: u64,
+    pub indexed: Option<u64>
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub queued: u64,
+    pub per_second: f64,
+    pub eta: Option<Duration>
+}
+
+
+impl Estimate {
+    pub fn new(queued: u64, per_second: f64) -> Self {
+	// Far off estimates are out of range for a Duration and left unknown.
+	let eta = if per_second > 0.0 {
+	    Duration::try_seconds((queued as f64 / per_second).ceil() as i64)
+	} else {
+	    None
+	};
+	Estimate {
+	    queued,
+	    per_second,
+	    eta
+	}
+    }
+
+    pub fn describe(&self) -> String {
+	let eta = match self.eta {
+	    Some(eta) => format_duration(eta),
+	    None => "unknown".to_owned(),
+	};
+	format!(
+	    "{} queued, {:.1} UUIDs/s, ETA {}",
+	    self.queued,
+	    self.per_second,
+	    eta
+	)
+    }
+}
+
+
+// Renders a duration as `1h 05m`, `4m 20s` or `35s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+	format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+	format!("{}m {:02}s", minutes, seconds)
+    } else {
+	format!("{}s", seconds)
+    }
+}
+
+
+// Estimates throughput from a single indexer response: `indexed` items done
+// in `elapsed` since the cycle started, with `queued` still left.
+pub fn estimate_from_cycle(queued: u64, indexed: u64, elapsed: Duration) -> Option<Estimate> {
+    let seconds = elapsed.num_milliseconds() as f64 / 1000.0;
+    if seconds <= 0.0 {
+	return None;
+    }
+    Some(Estimate::new(queued, indexed as f64 / seconds))
+}
+
+
+// Keeps a sliding window of indexer samples and estimates throughput over it.
+// Throughput comes from the growth of the indexed count when the indexer
+// reports one within a single cycle, otherwise from the shrinking queue.
+#[derive(Debug, Default)]
+pub struct Progress {
+    samples: VecDeque<Sample>,
+    last_report: Option<DateTime<Utc>>
+}
+
+
+impl Progress {
+    pub fn record(&mut self, sample: Sample) {
+	if self.samples.len() >= constants::PROGRESS_WINDOW {
+	    self.samples.pop_front();
+	}
+	self.samples.push_back(sample);
+    }
+
+    fn indexed_delta(&self, first: &Sample, last: &Sample) -> Option<u64> {
+	let mut previous = first.indexed?;
+	for sample in self.samples.iter().skip(1) {
+	    let indexed = sample.indexed?;
+	    if indexed < previous {
+		return None;
+	    }
+	    previous = indexed;
+	}
+	Some(last.indexed? - first.indexed?)
+    }
+
+    pub fn estimate(&self) -> Option<Estimate> {
+	let (first, last) = (self.samples.front()?, self.samples.back()?);
+	let seconds = (last.at - first.at).num_milliseconds() as f64 / 1000.0;
+	if seconds <= 0.0 {
+	    return None;
+	}
+	let done = match self.indexed_delta(first, last) {
+	    Some(delta) => delta as f64,
+	    None => first.queued as f64 - last.queued as f64,
+	};
+	Some(Estimate::new(last.queued, done.max(0.0) / seconds))
+    }
+
+    // Returns an estimate once every PROGRESS_UPDATE_INTERVAL seconds, counted
+    // from the first sample.
+    pub fn report_due(&mut self, now: DateTime<Utc>) -> Option<Estimate> {
+	let since = self.last_report.or_else(|| self.samples.front().map(|sample| sample.at))?;
+	if now - since < Duration::seconds(constants::PROGRESS_UPDATE_INTERVAL) {
+	    return None;
+	}
+	let estimate = self.estimate()?;
+	self.last_report = Some(now);
+	Some(estimate)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+
+    fn make_sample(seconds: i64, queued: u64, indexed: Option<u64>) -> Sample {
+	Sample {
+	    at: Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap() + Duration::seconds(seconds),
+	    queued,
+	    indexed
+	}
+    }
+
+
+    #[test]
+    fn test_format_duration() {
+	assert_eq!(format_duration(Duration::seconds(35)), "35s");
+	assert_eq!(format_duration(Duration::seconds(260)), "4m 20s");
+	assert_eq!(format_duration(Duration::seconds(3900)), "1h 05m");
+	assert_eq!(format_duration(Duration::seconds(-5)), "0s");
+    }
+
+
+    #[test]
+    fn test_estimate_needs_two_samples() {
+	let mut progress = Progress::default();
+	assert_eq!(progress.estimate(), None);
+	progress.record(make_sample(0, 1000, None));
+	assert_eq!(progress.estimate(), None);
+    }
+
+
+    #[test]
+    fn test_estimate_from_queue() {
+	let mut progress = Progress::default();
+	progress.record(make_sample(0, 1000, None));
+	progress.record(make_sample(10, 900, None));
+	progress.record(make_sample(20, 800, None));
+	let estimate = progress.estimate().unwrap();
+	assert_eq!(estimate.per_second, 10.0);
+	assert_eq!(estimate.eta, Some(Duration::seconds(80)));
+	assert_eq!(estimate.describe(), "800 queued, 10.0 UUIDs/s, ETA 1m 20s");
+    }
+
+
+    #[test]
+    fn test_estimate_prefers_indexed_count() {
+	let mut progress = Progress::default();
+	progress.record(make_sample(0, 1000, Some(0)));
+	progress.record(make_sample(10, 1100, Some(200)));
+	let estimate = progress.estimate().unwrap();
+	assert_eq!(estimate.per_second, 20.0);
+	assert_eq!(estimate.eta, Some(Duration::seconds(55)));
+	progress.record(make_sample(20, 1000, Some(50)));
+	assert_eq!(progress.estimate().unwrap().per_second, 0.0);
+    }
+
+
+    #[test]
+    fn test_estimate_without_progress_has_no_eta() {
+	let mut progress = Progress::default();
+	progress.record(make_sample(0, 1000, None));
+	progress.record(make_sample(10, 1200, None));
+	let estimate = progress.estimate().unwrap();
+	assert_eq!(estimate.eta, None);
+	assert_eq!(estimate.describe(), "1200 queued, 0.0 UUIDs/s, ETA unknown");
+    }
+
+
+    #[test]
+    fn test_window_drops_old_samples() {
+	let mut progress = Progress::default();
+	progress.record(make_sample(0, 100_000, None));
+	for i in 1..=constants::PROGRESS_WINDOW as i64 {
+	    progress.record(make_sample(i * 10, 1000 - i as u64 * 10, None));
+	}
+	assert_eq!(progress.estimate().unwrap().per_second, 1.0);
+    }
+
+
+    #[test]
+    fn test_report_due() {
+	let mut progress = Progress::default();
+	let interval = constants::PROGRESS_UPDATE_INTERVAL;
+	assert_eq!(progress.report_due(make_sample(0, 0, None).at), None);
+	progress.record(make_sample(0, 1000, None));
+	progress.record(make_sample(interval - 1, 900, None));
+	assert_eq!(progress.report_due(make_sample(interval - 1, 0, None).at), None);
+	progress.record(make_sample(interval, 800, None));
+	assert_eq!(progress.report_due(make_sample(interval, 0, None).at).unwrap().queued, 800);
+	assert_eq!(progress.report_due(make_sample(interval + 1, 0, None).at), None);
+	progress.record(make_sample(2 * interval, 700, None));
+	assert!(progress.report_due(make_sample(2 * interval, 0, None).at).is_some());
+    }
+
+
+    #[test]
+    fn test_estimate_from_cycle() {
+	let estimate = estimate_from_cycle(600, 1200, Duration::minutes(2)).unwrap();
+	assert_eq!(estimate.per_second, 10.0);
+	assert_eq!(estimate.eta, Some(Duration::minutes(1)));
+	assert_eq!(estimate_from_cycle(600, 0, Duration::zero()), None);
+    }
+
+
+    #[test]
+    fn test_estimate_too_far_off_has_no_eta() {
+	let estimate = Estimate::new(u64::MAX, 1e-12);
+	assert_eq!(estimate.eta, None);
+	assert!(estimate.describe().ends_with("ETA unknown"));
+	assert_eq!(estimate_from_cycle(u64::MAX, 1, Duration::days(365)).unwrap().eta, None);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::bot::RustyBot;
+use crate::constants;
+use clap::{App, AppSettings, Arg, ArgMatches};
+use regex::Regex;
+use slack_api::MessageStandard;
+use strsim::osa_distance;
+
+
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn aliases(&self) -> Vec<&'static str> {
+	vec![]
+    }
+
+    fn usage(&self) -> &'static str {
+	""
+    }
+
+    fn about(&self) -> &'static str;
+
+    fn examples(&self) -> Vec<&'static str> {
+	vec![]
+    }
+
+    fn args(&self) -> Vec<Arg<'static, 'static>> {
+	vec![]
+    }
+
+    fn subcommands(&self) -> &[Box<dyn Command>] {
+	&[]
+    }
+
+    fn run(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard);
+}
+
+
+fn make_subcommand(command: &dyn Command) -> App<'static, 'static> {
+    let mut app = App::new(command.name())
+	.about(command.about())
+	.aliases(&command.aliases())
+	.args(&command.args());
+    for subcommand in command.subcommands() {
+	app = app.subcommand(make_subcommand(subcommand.as_ref()));
+    }
+    app
+}
+
+
+fn find_command<'r>(commands: &'r [Box<dyn Command>], name: &str) -> Option<&'r dyn Command> {
+    commands.iter()
+	.map(|command| command.as_ref())
+	.find(|command| command.name() == name || command.aliases().contains(&name))
+}
+
+
+fn format_usage(path: &str, command: &dyn Command) -> String {
+    format!("*{}*", format!("{} {}", path, command.usage()).trim_end())
+}
+
+
+fn format_command_help(path: &str, command: &dyn Command) -> String {
+    let mut help = vec![
+	format_usage(path, command),
+	command.about().to_owned(),
+    ];
+    if !command.aliases().is_empty() {
+	help.push(format!("ALIASES: {}", command.aliases().join(", ")));
+    }
+    if command.subcommands().is_empty() {
+	let mut arguments = vec![];
+	let app = make_subcommand(command).template("{all-args}");
+	if app.write_help(&mut arguments).is_ok() && !arguments.is_empty() {
+	    help.push(format!("```{}```", String::from_utf8_lossy(&arguments).trim_end()));
+	}
+    } else {
+	help.push("COMMANDS:".to_owned());
+	for subcommand in command.subcommands() {
+	    help.push(format!(
+		"    {}     {}",
+		format_usage(&format!("{} {}", path, subcommand.name()), subcommand.as_ref()),
+		subcommand.about()
+	    ));
+	}
+    }
+    if !command.examples().is_empty() {
+	help.push("EXAMPLES:".to_owned());
+	for example in command.examples() {
+	    help.push(format!("    {} {}", constants::BOT_NAME, example));
+	}
+    }
+    help.join("\n")
+}
+
+
+fn dispatch_command(command: &dyn Command, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+    if let (name, Some(submatches)) = matches.subcommand() {
+	if let Some(subcommand) = find_command(command.subcommands(), name) {
+	    return dispatch_command(subcommand, bot, submatches, message);
+	}
+    }
+    command.run(bot, matches, message);
+}
+
+
+fn format_names(command: &dyn Command) -> String {
+    let mut names = vec![command.name()];
+    names.extend(command.aliases());
+    names.join("/")
+}
+
+
+fn flag_names(command: &dyn Command) -> Vec<String> {
+    lazy_static! {
+        static ref FLAG_RE: Regex = Regex::new(r"(?:^|[\s,])(--?[0-9A-Za-z][-0-9A-Za-z]*)").unwrap();
+    }
+    let mut options = vec![];
+    let app = make_subcommand(command).template("{flags}\n{options}");
+    if app.write_help(&mut options).is_err() {
+	return vec![];
+    }
+    FLAG_RE.captures_iter(&String::from_utf8_lossy(&options))
+	.map(|capture| capture[1].to_owned())
+	.collect()
+}
+
+
+// The candidates tied for the smallest edit distance from `value`, as long
+// as that distance is small relative to the candidate.
+fn closest<'c>(value: &str, candidates: Vec<&'c str>) -> Vec<&'c str> {
+    let mut scored = candidates.into_iter()
+	.map(|candidate| (osa_distance(value, candidate), candidate))
+	.filter(|(distance, candidate)| *distance <= (candidate.len() / 2).min(constants::MAX_SUGGESTION_DISTANCE))
+	.collect::<Vec<_>>();
+    scored.sort();
+    scored.dedup();
+    let best = scored.first().map(|(distance, _)| *distance);
+    scored.into_iter()
+	.filter(|(distance, _)| Some(*distance) == best)
+	.map(|(_, candidate)| candidate)
+	.collect()
+}
+
+
+fn format_suggestions(kind: &str, value: &str, suggestions: Vec<&str>, help: &str) -> String {
+    if suggestions.is_empty() {
+	return format!("Unknown {} `{}`, try `{}`", kind, value, help);
+    }
+    let suggestions = suggestions.iter()
+	.map(|suggestion| format!("`{}`", suggestion))
+	.collect::<Vec<_>>();
+    format!("Unknown {} `{}`. Did you mean {}?", kind, value, suggestions.join(" or "))
+}
+
+
+fn command_names(commands: &[Box<dyn Command>]) -> Vec<&'static str> {
+    let mut names = vec![];
+    for command in commands {
+	names.push(command.name());
+	names.extend(command.aliases());
+    }
+    names
+}
+
+
+fn suggest_flag(command: &dyn Command, path: &str, args: &[String]) -> Option<String> {
+    let flags = flag_names(command);
+    for arg in args {
+	if arg == "--" {
+	    break;
+	}
+	if !arg.starts_with('-') || arg == "-" || arg[1..].parse::<f64>().is_ok() {
+	    continue;
+	}
+	let flag = match arg.starts_with("--") {
+	    true => arg.split('=').next().unwrap().to_owned(),
+	    false => arg.chars().take(2).collect::<String>(),
+	};
+	if !flags.contains(&flag) {
+	    let long_flags = flags.iter()
+		.filter(|known| known.starts_with("--"))
+		.map(|known| known.as_str())
+		.collect();
+	    let suggestions = match flag.starts_with("--") {
+		true => closest(&flag, long_flags),
+		false => vec![],
+	    };
+	    let kind = format!("flag for `{}`", path);
+	    return Some(format_suggestions(&kind, &flag, suggestions, &format!("help {}", path)));
+	}
+    }
+    None
+}
+
+
+pub fn parse_aliases(value: &str) -> Vec<(String, String)> {
+    value.split(',')
+	.filter_map(|alias| alias.split_once('='))
+	.map(|(alias, expansion)| (alias.trim().to_owned(), expansion.trim().to_owned()))
+	.filter(|(alias, expansion)| !alias.is_empty() && !expansion.is_empty())
+	.collect()
+}
+
+
+fn push_help_lines(command: &dyn Command, prefix: &str, lines: &mut Vec<String>, examples: &mut Vec<String>) {
+    let path = format!("{}{}", prefix, format_names(command));
+    if command.subcommands().is_empty() {
+	let usage = format!("{} {}", path, command.usage());
+	lines.push(format!("    *{}*     {}", usage.trim_end(), command.about()));
+    }
+    for example in command.examples() {
+	examples.push(format!("    {} {}", constants::BOT_NAME, example));
+    }
+    for subcommand in command.subcommands() {
+	push_help_lines(subcommand.as_ref(), &format!("{} ", command.name()), lines, examples);
+    }
+}
+
+
+#[derive(Default)]
+pub struct Registry {
+    commands: Vec<Box<dyn Command>>,
+    aliases: Vec<(String, String)>
+}
+
+
+impl Registry {
+    pub fn new() -> Self {
+	Registry::default()
+    }
+
+    pub fn register(&mut self, command: Box<dyn Command>) {
+	self.commands.push(command);
+    }
+
+    // Aliases rewrite the first word of a command, e.g. `kill` to `cancel`
+    // or `info` to `ec2 info`, before it is parsed. They may not shadow a
+    // registered command.
+    pub fn add_alias(&mut self, alias: &str, expansion: &str) -> Result<(), String> {
+	if command_names(&self.commands).contains(&alias) {
+	    return Err(format!("Alias `{}` would shadow the `{}` command", alias, alias));
+	}
+	self.aliases.retain(|(existing, _)| existing != alias);
+	self.aliases.push((alias.to_owned(), expansion.to_owned()));
+	Ok(())
+    }
+
+    pub fn expand_alias(&self, args: Vec<String>) -> Vec<String> {
+	if let Some((first, rest)) = args.split_first() {
+	    if let Some((_, expansion)) = self.aliases.iter().find(|(alias, _)| alias == first) {
+		let mut expanded = expansion.split_whitespace().map(|x| x.to_owned()).collect::<Vec<_>>();
+		expanded.extend(rest.iter().cloned());
+		return expanded;
+	    }
+	}
+	args
+    }
+
+    // Explains why `args` did not parse when a command or flag is unknown,
+    // suggesting the closest registered names.
+    pub fn suggest(&self, args: &[String]) -> Option<String> {
+	let (first, mut rest) = args.split_first()?;
+	let mut command = match find_command(&self.commands, first) {
+	    Some(command) => command,
+	    None => {
+		let mut candidates = command_names(&self.commands);
+		candidates.extend(self.aliases.iter().map(|(alias, _)| alias.as_str()));
+		return Some(format_suggestions("command", first, closest(first, candidates), "help"));
+	    }
+	};
+	let mut path = vec![command.name()];
+	while !command.subcommands().is_empty() {
+	    let (name, remaining) = rest.split_first()?;
+	    command = match find_command(command.subcommands(), name) {
+		Some(subcommand) => subcommand,
+		None => {
+		    let candidates = command_names(command.subcommands());
+		    let kind = format!("`{}` command", path.join(" "));
+		    let help = format!("help {}", path.join(" "));
+		    return Some(format_suggestions(&kind, name, closest(name, candidates), &help));
+		}
+	    };
+	    path.push(command.name());
+	    rest = remaining;
+	}
+	suggest_flag(command, &path.join(" "), rest)
+    }
+
+    pub fn make_app(&self) -> App<'static, 'static> {
+	let mut app = App::new("Rustybot")
+	    .global_setting(AppSettings::ColorNever)
+	    .setting(AppSettings::DisableHelpSubcommand)
+	    .setting(AppSettings::DisableVersion)
+	    .setting(AppSettings::VersionlessSubcommands);
+	for command in self.commands.iter() {
+	    app = app.subcommand(make_subcommand(command.as_ref()));
+	}
+	app
+    }
+
+    pub fn dispatch(&self, bot: &RustyBot, matches: &ArgMatches, message: &MessageStandard) {
+	if let (name, Some(submatches)) = matches.subcommand() {
+	    if let Some(command) = find_command(&self.commands, name) {
+		dispatch_command(command, bot, submatches, message);
+	    }
+	}
+    }
+
+    // Walks a command path such as `["ec2", "resize"]`, accepting aliases, and
+    // returns the canonical path alongside the command.
+    pub fn find(&self, path: &[&str]) -> Option<(String, &dyn Command)> {
+	let (first, rest) = path.split_first()?;
+	let mut command = find_command(&self.commands, first)?;
+	let mut names = vec![command.name()];
+	for name in rest {
+	    command = find_command(command.subcommands(), name)?;
+	    names.push(command.name());
+	}
+	Some((names.join(" "), command))
+    }
+
+    pub fn usage(&self, path: &[&str]) -> Option<String> {
+	self.find(path).map(|(path, command)| format_usage(&path, command))
+    }
+
+    pub fn command_help(&self, path: &[&str]) -> Option<String> {
+	self.find(path).map(|(path, command)| format_command_help(&path, command))
+    }
+
+    pub fn help(&self) -> String {
+	let mut lines = vec![];
+	let mut examples = vec![];
+	for command in self.commands.iter() {
+	    push_help_lines(command.as_ref(), "", &mut lines, &mut examples);
+	}
+	let mut aliases = String::new();
+	if !self.aliases.is_empty() {
+	    aliases.push_str("ALIASES:\n");
+	    for (alias, expansion) in self.aliases.iter() {
+		aliases.push_str(&format!("    *{}*     Same as `{}`\n", alias, expansion));
+	    }
+	}
+	format!(
+	    "\nUSAGE:\n    {} [COMMAND]\nCOMMANDS:\n{}\n{}{}EXAMPLES:\n{}\n{}",
+	    constants::BOT_NAME,
+	    lines.join("\n"),
+	    constants::DELAY_HELP,
+	    aliases,
+	    examples.join("\n"),
+	    constants::DELAY_EXAMPLES
+	)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::make_registry;
+
+
+    #[test]
+    fn test_help_lists_nested_commands_and_aliases() {
+	let help = make_registry().help();
+	assert!(help.contains("    *stop/cancel [JOB_ID]*     Cancel job\n"));
+	assert!(help.contains("    *monitor [URL...] [OPTIONS]*     Monitor URL until indexing is complete\n"));
+	assert!(help.contains("    *ec2 resize [INSTANCE] -s/--size [SIZE] --stop --start*     Resize instance (default r5.2xlarge)\n"));
+	assert!(help.contains("    <@rustybot> ec2 info i-02e86c27e5d31f8d1\n"));
+	assert!(!help.contains("*ec2*"));
+    }
+
+
+    #[test]
+    fn test_make_app_parses_registered_commands() {
+	let matches = make_registry().make_app().get_matches_from_safe(
+	    vec!["<@rustybot>", "ec2", "ls", "-f", "instance-type=t2.micro", "-l", "5"]
+	).unwrap();
+	let (name, ec2) = matches.subcommand();
+	assert_eq!(name, "ec2");
+	let (name, ls) = ec2.unwrap().subcommand();
+	assert_eq!(name, "ls");
+	assert_eq!(ls.unwrap().value_of("limit"), Some("5"));
+	let matches = make_registry().make_app().get_matches_from_safe(
+	    vec!["<@rustybot>", "cancel", "1234"]
+	).unwrap();
+	assert_eq!(matches.subcommand_name(), Some("stop"));
+	let matches = make_registry().make_app().get_matches_from_safe(
+	    vec!["<@rustybot>", "help", "ec2", "resize"]
+	).unwrap();
+	let help = matches.subcommand_matches("help").unwrap();
+	assert_eq!(help.values_of("command").unwrap().collect::<Vec<_>>(), vec!["ec2", "resize"]);
+    }
+
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+	args.iter().map(|x| x.to_string()).collect()
+    }
+
+
+    #[test]
+    fn test_suggest_commands() {
+	let registry = make_registry();
+	assert_eq!(
+	    registry.suggest(&to_args(&["kornitor", "https://test.encodedcc.org/"])),
+	    Some("Unknown command `kornitor`. Did you mean `konitor` or `kronitor`?".to_owned())
+	);
+	assert_eq!(
+	    registry.suggest(&to_args(&["ec2", "resise", "i-02e86c27e5d31f8d1"])),
+	    Some("Unknown `ec2` command `resise`. Did you mean `resize`?".to_owned())
+	);
+	assert_eq!(
+	    registry.suggest(&to_args(&["wtach"])),
+	    Some("Unknown command `wtach`. Did you mean `watch`?".to_owned())
+	);
+	assert_eq!(
+	    registry.suggest(&to_args(&["explode"])),
+	    Some("Unknown command `explode`, try `help`".to_owned())
+	);
+    }
+
+
+    #[test]
+    fn test_suggest_flags() {
+	let registry = make_registry();
+	assert_eq!(
+	    registry.suggest(&to_args(&["kronitor", "https://test.encodedcc.org/", "--sise", "c5.4xlarge"])),
+	    Some("Unknown flag for `kronitor` `--sise`. Did you mean `--size`?".to_owned())
+	);
+	assert_eq!(
+	    registry.suggest(&to_args(&["ec2", "ls", "-x", "3"])),
+	    Some("Unknown flag for `ec2 ls` `-x`, try `help ec2 ls`".to_owned())
+	);
+	assert_eq!(registry.suggest(&to_args(&["ec2", "ls", "-f", "instance-type=t2.micro", "--limit=3"])), None);
+	assert_eq!(registry.suggest(&to_args(&["ec2"])), None);
+    }
+
+
+    #[test]
+    fn test_expand_alias() {
+	let mut registry = make_registry();
+	registry.add_alias("info", "ec2 info").unwrap();
+	assert_eq!(registry.expand_alias(to_args(&["kill", "1234"])), to_args(&["cancel", "1234"]));
+	assert_eq!(registry.expand_alias(to_args(&["info", "URL"])), to_args(&["ec2", "info", "URL"]));
+	assert_eq!(registry.expand_alias(to_args(&["status", "kill"])), to_args(&["status", "kill"]));
+	assert!(registry.help().contains("ALIASES:\n    *kill*     Same as `cancel`\n"));
+    }
+
+
+    #[test]
+    fn test_alias_cannot_shadow_command() {
+	let mut registry = make_registry();
+	assert_eq!(
+	    registry.add_alias("monitor", "ec2 terminate"),
+	    Err("Alias `monitor` would shadow the `monitor` command".to_owned())
+	);
+	assert!(registry.add_alias("cancel", "ec2 terminate").is_err());
+	assert_eq!(registry.expand_alias(to_args(&["monitor", "URL"])), to_args(&["monitor", "URL"]));
+	assert!(!registry.help().contains("*monitor*     Same as"));
+    }
+
+
+    #[test]
+    fn test_parse_aliases() {
+	assert_eq!(
+	    parse_aliases("watch=monitor, info = ec2 info,broken,=empty"),
+	    vec![("watch".to_owned(), "monitor".to_owned()), ("info".to_owned(), "ec2 info".to_owned())]
+	);
+    }
+
+
+    #[test]
+    fn test_command_help() {
+	let registry = make_registry();
+	let help = registry.command_help(&["ec2", "resize"]).unwrap();
+	assert!(help.starts_with("*ec2 resize [INSTANCE] -s/--size [SIZE] --stop --start*\nResize instance"));
+	assert!(help.contains("--size <size>"));
+	assert!(help.contains("    <@rustybot> ec2 resize i-02e86c27e5d31f8d1 --size c5.9xlarge"));
+	let help = registry.command_help(&["cancel"]).unwrap();
+	assert!(help.starts_with("*stop [JOB_ID]*\nCancel job\nALIASES: cancel"));
+	let help = registry.command_help(&["ec2"]).unwrap();
+	assert!(help.contains("    *ec2 info [INSTANCE]*     Get instance info"));
+	assert!(registry.command_help(&["ec2", "explode"]).is_none());
+	assert_eq!(registry.usage(&["status"]), Some("*status [URL]*".to_owned()));
+    }
+}
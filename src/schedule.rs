@@ -0,0 +1,267 @@
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use slack_api::MessageStandard;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use regex::Regex;
+use crate::constants;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub worker_id: String,
+    pub fire_at: DateTime<Utc>,
+    pub channel: String,
+    pub text: String,
+    pub user: Option<String>
+}
+
+
+impl ScheduledJob {
+    pub fn to_message(&self) -> MessageStandard {
+	serde_json::from_value(
+	    serde_json::json!({
+		"channel": self.channel,
+		"text": self.text,
+		"user": self.user,
+	    })
+	).unwrap()
+    }
+
+    pub fn command(&self) -> &str {
+	self.text.trim_start_matches(constants::BOT_ID).trim()
+    }
+
+    pub fn describe(&self) -> String {
+	format!("{} (fires at {})", self.command(), format_fire_at(&self.fire_at))
+    }
+
+    // A job is missed when it fires well after its time, e.g. because the bot
+    // was down.
+    pub fn is_missed(&self, now: DateTime<Utc>) -> bool {
+	now - self.fire_at > Duration::seconds(constants::SCHEDULE_GRACE_PERIOD)
+    }
+}
+
+
+pub fn format_fire_at(fire_at: &DateTime<Utc>) -> String {
+    fire_at.with_timezone(&Local).format("%Y-%m-%d %H:%M %Z").to_string()
+}
+
+
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    lazy_static! {
+        static ref DURATION_RE: Regex = Regex::new(r"^(\d+[dhms])+$").unwrap();
+        static ref PART_RE: Regex = Regex::new(r"(\d+)([dhms])").unwrap();
+    }
+    if !DURATION_RE.is_match(value) {
+	return None;
+    }
+    let mut duration = Duration::zero();
+    for capture in PART_RE.captures_iter(value) {
+	let amount = capture[1].parse::<i64>().ok()?;
+	let part = match &capture[2] {
+	    "d" => Duration::try_days(amount),
+	    "h" => Duration::try_hours(amount),
+	    "m" => Duration::try_minutes(amount),
+	    _ => Duration::try_seconds(amount),
+	};
+	duration = duration.checked_add(&part?)?;
+    }
+    Some(duration)
+}
+
+
+fn parse_time_of_day(value: &str, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::parse_from_str(value, "%H:%M").ok()?;
+    let mut fire_at = Local
+	.from_local_datetime(&now.date_naive().and_time(time))
+	.earliest()?;
+    if fire_at <= now {
+	fire_at += Duration::days(1);
+    }
+    Some(fire_at.with_timezone(&Utc))
+}
+
+
+// Splits `in 90m ec2 stop URL` or `at 18:30 kronitor URL` into the time the
+// command should fire and the command itself.
+pub fn parse_delay(text: &str, now: DateTime<Local>) -> Option<(DateTime<Utc>, String)> {
+    lazy_static! {
+        static ref DELAY_RE: Regex = Regex::new(r"^\s*(in|at)\s+(\S+)\s+(?s)(.*\S)\s*$").unwrap();
+    }
+    let capture = DELAY_RE.captures(text)?;
+    let (keyword, value, command) = (&capture[1], &capture[2], &capture[3]);
+    let fire_at = match keyword {
+	"in" => {
+	    let delay = parse_duration(value).filter(|delay| *delay <= Duration::days(constants::MAX_DELAY_DAYS))?;
+	    now.with_timezone(&Utc).checked_add_signed(delay)?
+	},
+	"at" => parse_time_of_day(value, now)?,
+	_ => return None,
+    };
+    Some((fire_at, command.to_owned()))
+}
+
+
+pub struct Schedule {
+    path: PathBuf,
+    lock: Mutex<()>
+}
+
+
+impl Schedule {
+    pub fn new(path: PathBuf) -> Self {
+	Schedule {
+	    path,
+	    lock: Mutex::new(())
+	}
+    }
+
+    pub fn from_env() -> Self {
+	Schedule::new(
+	    env::var("RUSTY_BOT_SCHEDULE")
+		.unwrap_or_else(|_| constants::SCHEDULE_FILE.to_owned())
+		.into()
+	)
+    }
+
+    fn read(&self) -> Vec<ScheduledJob> {
+	fs::read_to_string(&self.path)
+	    .ok()
+	    .and_then(|contents| serde_json::from_str(&contents).ok())
+	    .unwrap_or_default()
+    }
+
+    fn write(&self, jobs: &[ScheduledJob]) {
+	match serde_json::to_string_pretty(jobs) {
+	    Ok(contents) => {
+		if let Err(error) = fs::write(&self.path, contents) {
+		    println!("Error writing schedule {:?}: {}", &self.path, error);
+		}
+	    },
+	    Err(error) => println!("Error serializing schedule: {}", error),
+	}
+    }
+
+    pub fn load(&self) -> Vec<ScheduledJob> {
+	let _guard = self.lock.lock().unwrap();
+	self.read()
+    }
+
+    pub fn add(&self, job: ScheduledJob) {
+	let _guard = self.lock.lock().unwrap();
+	let mut jobs = self.read();
+	jobs.push(job);
+	self.write(&jobs);
+    }
+
+    pub fn remove(&self, worker_id: &str) {
+	let _guard = self.lock.lock().unwrap();
+	let mut jobs = self.read();
+	let total = jobs.len();
+	jobs.retain(|job| job.worker_id != worker_id);
+	if jobs.len() != total {
+	    self.write(&jobs);
+	}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn make_now() -> DateTime<Local> {
+	Local.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap()
+    }
+
+
+    #[test]
+    fn test_parse_duration() {
+	assert_eq!(parse_duration("90m"), Some(Duration::minutes(90)));
+	assert_eq!(parse_duration("2h"), Some(Duration::hours(2)));
+	assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+	assert_eq!(parse_duration("1d12h"), Some(Duration::hours(36)));
+	assert_eq!(parse_duration("45s"), Some(Duration::seconds(45)));
+	assert_eq!(parse_duration("2x"), None);
+	assert_eq!(parse_duration("h"), None);
+	assert_eq!(parse_duration("ec2"), None);
+	assert_eq!(parse_duration("9999999999999d"), None);
+	assert_eq!(parse_duration("99999999999999999999s"), None);
+	assert_eq!(parse_duration("106751991167300d1d"), None);
+    }
+
+
+    #[test]
+    fn test_parse_delay_in() {
+	let now = make_now();
+	let (fire_at, command) = parse_delay(
+	    "in 90m ec2 stop https://test.encodedcc.org/",
+	    now
+	).unwrap();
+	assert_eq!(fire_at, now.with_timezone(&Utc) + Duration::minutes(90));
+	assert_eq!(command, "ec2 stop https://test.encodedcc.org/");
+    }
+
+
+    #[test]
+    fn test_parse_delay_at() {
+	let now = make_now();
+	let (fire_at, command) = parse_delay("at 18:30 kronitor URL -s c5.4xlarge", now).unwrap();
+	assert_eq!(fire_at, Local.with_ymd_and_hms(2020, 6, 1, 18, 30, 0).unwrap().with_timezone(&Utc));
+	assert_eq!(command, "kronitor URL -s c5.4xlarge");
+	let (fire_at, command) = parse_delay(" at  9:15\tmonitor \"URL\"\n", now).unwrap();
+	assert_eq!(command, "monitor \"URL\"");
+	assert_eq!(fire_at, Local.with_ymd_and_hms(2020, 6, 2, 9, 15, 0).unwrap().with_timezone(&Utc));
+    }
+
+
+    #[test]
+    fn test_parse_delay_rejects_commands() {
+	let now = make_now();
+	assert_eq!(parse_delay("monitor https://test.encodedcc.org/", now), None);
+	assert_eq!(parse_delay("in 90m", now), None);
+	assert_eq!(parse_delay("in 90m   ", now), None);
+	assert_eq!(parse_delay("in soon monitor URL", now), None);
+	assert_eq!(parse_delay("at 25:00 monitor URL", now), None);
+	assert_eq!(parse_delay("in 9999999999999d status URL", now), None);
+	assert_eq!(parse_delay("in 31d status URL", now), None);
+	assert!(parse_delay("in 30d status URL", now).is_some());
+    }
+
+
+    #[test]
+    fn test_scheduled_job_to_message() {
+	let job = ScheduledJob {
+	    worker_id: "1001".to_owned(),
+	    fire_at: make_now().with_timezone(&Utc),
+	    channel: "C123".to_owned(),
+	    text: format!("{} ec2 stop i-02e86c27e5d31f8d1", constants::BOT_ID),
+	    user: Some("U123".to_owned())
+	};
+	let message = job.to_message();
+	assert_eq!(message.channel, Some("C123".to_owned()));
+	assert_eq!(message.text, Some(job.text.clone()));
+	assert_eq!(message.user, Some("U123".to_owned()));
+	assert!(job.describe().starts_with("ec2 stop i-02e86c27e5d31f8d1 (fires at "));
+    }
+
+
+    #[test]
+    fn test_scheduled_job_is_missed() {
+	let job = ScheduledJob {
+	    worker_id: "1001".to_owned(),
+	    fire_at: make_now().with_timezone(&Utc),
+	    channel: "C123".to_owned(),
+	    text: format!("{} kronitor URL", constants::BOT_ID),
+	    user: None
+	};
+	assert!(!job.is_missed(job.fire_at - Duration::hours(1)));
+	assert!(!job.is_missed(job.fire_at + Duration::seconds(constants::SCHEDULE_GRACE_PERIOD)));
+	assert!(job.is_missed(job.fire_at + Duration::hours(3)));
+    }
+}
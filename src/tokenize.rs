@@ -0,0 +1,196 @@
+use std::error::Error;
+use std::fmt;
+
+
+#[derive(Debug, PartialEq)]
+pub enum TokenizeError {
+    UnterminatedQuote(char),
+    UnterminatedMarkup,
+    TrailingEscape
+}
+
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    TokenizeError::UnterminatedQuote(quote) => write!(f, "Missing closing {}", quote),
+	    TokenizeError::UnterminatedMarkup => write!(f, "Missing closing >"),
+	    TokenizeError::TrailingEscape => write!(f, "Nothing to escape after trailing \\"),
+	}
+    }
+}
+
+
+impl Error for TokenizeError {}
+
+
+pub(crate) fn closing_quote(quote: char) -> Option<char> {
+    match quote {
+	'"' => Some('"'),
+	'\'' => Some('\''),
+	'“' => Some('”'),
+	'‘' => Some('’'),
+	_ => None
+    }
+}
+
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+	.replace("&gt;", ">")
+	.replace("&amp;", "&")
+}
+
+
+// Slack wraps links, mentions and channels as `<target|label>`. Links keep
+// their target, mentions and channels prefer the label when one is given.
+pub fn unwrap_slack_markup(markup: &str) -> String {
+    let markup = decode_entities(markup);
+    let (target, label) = match markup.split_once('|') {
+	Some((target, label)) => (target, Some(label)),
+	None => (markup.as_str(), None),
+    };
+    match target.chars().next() {
+	Some('@') | Some('#') => match label {
+	    Some(label) => format!("{}{}", &target[..1], label.trim_start_matches(&target[..1])),
+	    None => target.to_owned(),
+	},
+	Some('!') => match label {
+	    Some(label) => label.to_owned(),
+	    None => format!("@{}", &target[1..]),
+	},
+	_ => target.to_owned(),
+    }
+}
+
+
+fn take_markup(chars: &mut dyn Iterator<Item = char>) -> Result<String, TokenizeError> {
+    let mut markup = String::new();
+    for c in chars {
+	if c == '>' {
+	    return Ok(unwrap_slack_markup(&markup));
+	}
+	markup.push(c);
+    }
+    Err(TokenizeError::UnterminatedMarkup)
+}
+
+
+fn take_entity(first: char, chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut entity = first.to_string();
+    while let Some(&c) = chars.peek() {
+	if entity.len() > 4 || !(c.is_ascii_alphabetic() || c == ';') {
+	    break;
+	}
+	entity.push(c);
+	chars.next();
+	if c == ';' {
+	    break;
+	}
+    }
+    decode_entities(&entity)
+}
+
+
+// Splits a message into arguments the way a shell would: whitespace separates
+// arguments, straight or curly quotes group them and `\` escapes the next
+// character. Slack markup and HTML entities are unwrapped along the way.
+pub fn tokenize(text: &str) -> Result<Vec<String>, TokenizeError> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+	match c {
+	    '\\' => {
+		current.push(chars.next().ok_or(TokenizeError::TrailingEscape)?);
+		in_token = true;
+	    },
+	    '<' => {
+		current.push_str(&take_markup(&mut chars)?);
+		in_token = true;
+	    },
+	    '&' => {
+		current.push_str(&take_entity(c, &mut chars));
+		in_token = true;
+	    },
+	    _ if quote.is_some() => {
+		if Some(c) == quote.and_then(closing_quote) {
+		    quote = None;
+		} else {
+		    current.push(c);
+		}
+	    },
+	    _ if closing_quote(c).is_some() => {
+		quote = Some(c);
+		in_token = true;
+	    },
+	    _ if c.is_whitespace() => {
+		if in_token {
+		    tokens.push(std::mem::take(&mut current));
+		    in_token = false;
+		}
+	    },
+	    _ => {
+		current.push(c);
+		in_token = true;
+	    }
+	}
+    }
+    if let Some(quote) = quote {
+	return Err(TokenizeError::UnterminatedQuote(closing_quote(quote).unwrap()));
+    }
+    if in_token {
+	tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_tokenize_whitespace() {
+	let tokens = tokenize("<@U013X667NR4>  monitor\n\thttps://test.encodedcc.org/ ").unwrap();
+	assert_eq!(tokens, vec!["@U013X667NR4", "monitor", "https://test.encodedcc.org/"]);
+	assert_eq!(tokenize("   ").unwrap(), Vec::<String>::new());
+    }
+
+
+    #[test]
+    fn test_tokenize_quotes_and_escapes() {
+	let tokens = tokenize(r#"ec2 ls -f "tag:Name=foo bar" -f 'a b' -f “c d” -f x\ y -f """#).unwrap();
+	assert_eq!(
+	    tokens,
+	    vec!["ec2", "ls", "-f", "tag:Name=foo bar", "-f", "a b", "-f", "c d", "-f", "x y", "-f", ""]
+	);
+	assert_eq!(tokenize(r#"say "unfinished"#), Err(TokenizeError::UnterminatedQuote('"')));
+	assert_eq!(tokenize("say ‘unfinished"), Err(TokenizeError::UnterminatedQuote('’')));
+	assert_eq!(tokenize("trailing \\"), Err(TokenizeError::TrailingEscape));
+    }
+
+
+    #[test]
+    fn test_tokenize_slack_markup() {
+	let tokens = tokenize(
+	    "status <https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/|encd-5328-3a048a0ae-emma.demo.encodedcc.org>"
+	).unwrap();
+	assert_eq!(tokens, vec!["status", "https://encd-5328-3a048a0ae-emma.demo.encodedcc.org/"]);
+	let tokens = tokenize("<@U123> <@U456|keenan> <#C789|general> <#C789> <!here>").unwrap();
+	assert_eq!(tokens, vec!["@U123", "@keenan", "#general", "#C789", "@here"]);
+	let tokens = tokenize("-f \"tag:Name=<http://foo.org|foo.org> bar\"").unwrap();
+	assert_eq!(tokens, vec!["-f", "tag:Name=http://foo.org bar"]);
+	assert_eq!(tokenize("<http://foo.org"), Err(TokenizeError::UnterminatedMarkup));
+    }
+
+
+    #[test]
+    fn test_tokenize_entities() {
+	let tokens = tokenize("a&amp;b &lt;c&gt; <https://x.org/?a=1&amp;b=2> &other").unwrap();
+	assert_eq!(tokens, vec!["a&b", "<c>", "https://x.org/?a=1&b=2", "&other"]);
+    }
+}